@@ -1,7 +1,7 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
-use spirv_std::glam::{Vec4, vec3, Vec3, Mat4};
-use spirv_std::{spirv};
+use spirv_std::glam::{Vec4, Vec3, Vec2, Mat4};
+use spirv_std::{spirv, Image, Sampler};
 
 #[spirv(fragment)]
 pub fn main_fs(
@@ -11,17 +11,49 @@ pub fn main_fs(
     *output = in_colour.extend(1.00);
 }
 
-// const POSITIONS: [Vec2; 3] = [
-//     vec2(0.0, -0.5),
-//     vec2(0.5, 0.5),
-//     vec2(-0.5, 0.5)
-// ];
+type SampledImage2d = Image!(2D, format = rgba32f, sampled = true);
 
-const COLOURS: [Vec3; 3] = [
-    vec3(1.0, 0.0, 0.0),
-    vec3(0.0, 1.0, 0.0),
-    vec3(0.0, 0.0, 1.0)
-];
+/// Emits a clip-space fullscreen triangle (covering the whole viewport with
+/// no vertex buffer) and its matching UV, for post-processing passes that
+/// only need to resample a previous pass's output.
+#[spirv(vertex)]
+pub fn main_vs_fullscreen(
+    #[spirv(vertex_index)] vertex_index: i32,
+    #[spirv(position, invariant)] out_position: &mut Vec4,
+    out_uv: &mut Vec2
+) {
+    let uv = Vec2::new(((vertex_index << 1) & 2) as f32, (vertex_index & 2) as f32);
+    *out_uv = uv;
+    *out_position = (uv * 2.0 - Vec2::ONE).extend(0.0).extend(1.0);
+}
+
+/// Post-process pass: Reinhard tonemapping of the previous pass's output.
+#[spirv(fragment)]
+pub fn main_fs_post_tonemap(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 0)] input_color: &SampledImage2d,
+    #[spirv(descriptor_set = 0, binding = 1)] input_sampler: &Sampler,
+    output: &mut Vec4
+) {
+    let sample: Vec4 = input_color.sample(*input_sampler, in_uv);
+    let colour = sample.truncate();
+    let tonemapped = colour / (colour + Vec3::ONE);
+    *output = tonemapped.extend(sample.w);
+}
+
+/// Post-process pass: darkens the image towards its edges.
+#[spirv(fragment)]
+pub fn main_fs_post_vignette(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 0)] input_color: &SampledImage2d,
+    #[spirv(descriptor_set = 0, binding = 1)] input_sampler: &Sampler,
+    output: &mut Vec4
+) {
+    let sample: Vec4 = input_color.sample(*input_sampler, in_uv);
+    let from_centre = (in_uv - Vec2::splat(0.5)) * 2.0;
+    let vignette = 1.0 - from_centre.dot(from_centre) * 0.35;
+    *output = (sample.truncate() * vignette).extend(sample.w);
+}
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -38,15 +70,109 @@ pub struct GeoVertex {
     pub normal: Vec3
 }
 
+#[derive(Clone, Copy)]
+#[spirv(block)]
+#[repr(C)]
+pub struct Particle {
+    pub position: Vec3,
+    pub velocity: Vec3
+}
+
+/// Pre-pass particle simulation: a single explicit-Euler integration step,
+/// dispatched before the main render pass so its output buffer is ready to
+/// be read as vertex data.
+#[spirv(compute(threads(64)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] id: spirv_std::glam::UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] particles_in: &[Particle],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] particles_out: &mut [Particle],
+) {
+    let index = id.x as usize;
+    if index >= particles_in.len() {
+        return;
+    }
+    const DELTA_TIME: f32 = 1.0 / 60.0;
+    let current = particles_in[index];
+    particles_out[index] = Particle {
+        position: current.position + current.velocity * DELTA_TIME,
+        velocity: current.velocity
+    };
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     #[spirv(push_constant)] in_camera_uniforms: &CameraUniforms,
-    in_vertex: GeoVertex, //TODO what is the rust_gpu way of indexing the vertex buffer?
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] vertices: &[GeoVertex],
     #[spirv(vertex_index)] vertex_index: i32,
     #[spirv(position, invariant)] out_position: &mut Vec4,
     out_colour: &mut Vec3
 ) {
-    let current_vertex = in_vertex;
+    let current_vertex = vertices[vertex_index as usize];
     *out_position = in_camera_uniforms.proj * in_camera_uniforms.view * current_vertex.position.extend(1.0);
-    *out_colour = COLOURS[(vertex_index as usize) % 3];
+    // Simple normal-based shading until real material/lighting data exists.
+    *out_colour = current_vertex.normal * 0.5 + Vec3::splat(0.5);
+}
+
+/// Combined view-projection matrix, bound separately from `ViewUniform` so
+/// shading math that only needs the view transform (e.g. normals) doesn't
+/// have to unpick it back out of a projected matrix.
+#[derive(Clone, Copy)]
+#[spirv(block)]
+#[repr(C)]
+pub struct ViewProjUniform {
+    pub view_proj: Mat4
+}
+
+#[derive(Clone, Copy)]
+#[spirv(block)]
+#[repr(C)]
+pub struct ViewUniform {
+    pub view: Mat4
+}
+
+/// Identical to `main_vs`, except the camera matrices are read from a
+/// descriptor set (set 1, see `PipelineCtx::camera_descriptor_set_layout`)
+/// instead of push constants, split into `ViewProjUniform` and `ViewUniform`,
+/// and the colour comes from the view-space normal rather than the
+/// world-space one.
+#[spirv(vertex)]
+pub fn main_vs_descriptor_camera(
+    #[spirv(uniform, descriptor_set = 1, binding = 0)] view_proj: &ViewProjUniform,
+    #[spirv(uniform, descriptor_set = 1, binding = 1)] view: &ViewUniform,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] vertices: &[GeoVertex],
+    #[spirv(vertex_index)] vertex_index: i32,
+    #[spirv(position, invariant)] out_position: &mut Vec4,
+    out_colour: &mut Vec3
+) {
+    let current_vertex = vertices[vertex_index as usize];
+    *out_position = view_proj.view_proj * current_vertex.position.extend(1.0);
+    let view_space_normal = (view.view * current_vertex.normal.extend(0.0)).truncate();
+    *out_colour = view_space_normal * 0.5 + Vec3::splat(0.5);
+}
+
+/// One projection/view pair per `VK_KHR_multiview` view, e.g. left/right
+/// eyes for stereo VR or the two halves of a side-by-side output.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct StereoCameraUniforms {
+    pub proj: [Mat4; 2],
+    pub view: [Mat4; 2]
+}
+
+/// Identical to `main_vs`, except the projection/view pair is selected by
+/// `gl_ViewIndex`, which the render pass's view mask drives once per view
+/// within a single draw. Pair with `RenderPassCtx::new_multiview`.
+#[spirv(vertex)]
+pub fn main_vs_multiview(
+    #[spirv(push_constant)] in_camera_uniforms: &StereoCameraUniforms,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] vertices: &[GeoVertex],
+    #[spirv(vertex_index)] vertex_index: i32,
+    #[spirv(view_index)] view_index: i32,
+    #[spirv(position, invariant)] out_position: &mut Vec4,
+    out_colour: &mut Vec3
+) {
+    let current_vertex = vertices[vertex_index as usize];
+    let view_index = view_index as usize;
+    *out_position = in_camera_uniforms.proj[view_index] * in_camera_uniforms.view[view_index] * current_vertex.position.extend(1.0);
+    *out_colour = current_vertex.normal * 0.5 + Vec3::splat(0.5);
 }
\ No newline at end of file
@@ -1,16 +1,27 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use vulkano::{VulkanLibrary, instance::{Instance, InstanceCreateInfo}, device::{DeviceExtensions, Device, DeviceCreateInfo, QueueFlags, physical::{PhysicalDeviceType, PhysicalDevice}, QueueCreateInfo, Queue}, swapchain::{Swapchain, SwapchainCreateInfo, Surface, AcquireError, SwapchainPresentInfo}, image::{ImageUsage, SwapchainImage, view::ImageView}, render_pass::{RenderPass, Framebuffer, FramebufferCreateInfo, Subpass}, sync::{future::FenceSignalFuture, self, GpuFuture, FlushError}, pipeline::{graphics::{viewport::{Viewport, ViewportState}, input_assembly::InputAssemblyState, vertex_input::Vertex}, GraphicsPipeline}, shader::ShaderModule, command_buffer::{allocator::StandardCommandBufferAllocator}};
+use vulkano::{VulkanLibrary, instance::{Instance, InstanceCreateInfo}, device::{DeviceExtensions, Device, DeviceCreateInfo, DeviceFeatures, QueueFlags, physical::{PhysicalDeviceType, PhysicalDevice}, QueueCreateInfo, Queue}, swapchain::{Swapchain, SwapchainCreateInfo, Surface, AcquireError, SwapchainPresentInfo}, image::{ImageUsage, ImageType, ImageCreateInfo, Image, SwapchainImage, view::ImageView}, render_pass::{RenderPass, Framebuffer, FramebufferCreateInfo, Subpass}, sync::{future::FenceSignalFuture, self, GpuFuture, FlushError, semaphore::{Semaphore, SemaphoreCreateInfo, SemaphoreType, SemaphoreWaitInfo}}, pipeline::{graphics::{viewport::{Viewport, ViewportState}, input_assembly::InputAssemblyState, vertex_input::Vertex, depth_stencil::DepthStencilState}, GraphicsPipeline, PipelineShaderStageCreateInfo, PipelineLayout, ray_tracing::{RayTracingPipeline, RayTracingPipelineCreateInfo, RayTracingShaderGroupCreateInfo, ShaderBindingTable}}, shader::ShaderModule, command_buffer::{allocator::{StandardCommandBufferAllocator}, AutoCommandBufferBuilder, PrimaryAutoCommandBuffer, CommandBufferUsage}, buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer},
+command_buffer::CopyBufferInfo, memory::allocator::{StandardMemoryAllocator, AllocationCreateInfo, MemoryTypeFilter}, format::Format, acceleration_structure::{AccelerationStructure, AccelerationStructureCreateInfo, AccelerationStructureType, AccelerationStructureBuildGeometryInfo, AccelerationStructureGeometries, AccelerationStructureGeometryTrianglesData, AccelerationStructureGeometryInstancesData, AccelerationStructureGeometryInstancesDataType, AccelerationStructureInstance, AccelerationStructureBuildRangeInfo, BuildAccelerationStructureFlags, BuildAccelerationStructureMode, GeometryFlags}};
 use winit::{window::{Window}, dpi::PhysicalSize};
 
 use crate::{scene::{GeoVertex, Scene}};
 
+/// Device extensions required for the hardware ray tracing backend
+/// (`RayTracingCtx`), on top of `khr_swapchain`.
+pub const RAY_TRACING_EXTENSIONS: DeviceExtensions = DeviceExtensions {
+    khr_acceleration_structure: true,
+    khr_ray_tracing_pipeline: true,
+    khr_buffer_device_address: true,
+    khr_deferred_host_operations: true,
+    ..DeviceExtensions::empty()
+};
+
 pub struct DeviceCtx {
     surface: Arc<Surface>,
     physical_device: Arc<PhysicalDevice>,
     pub device: Arc<Device>,
-    queue: Arc<Queue>
+    pub queue: Arc<Queue>
 }
 
 impl DeviceCtx {
@@ -36,16 +47,26 @@ impl DeviceCtx {
                     })
                     .map(|q| (p, q as u32))
             })
-            .min_by_key(|(p, _)| match p.properties().device_type {
-                PhysicalDeviceType::DiscreteGpu => 0,
-                PhysicalDeviceType::IntegratedGpu => 1,
-                PhysicalDeviceType::VirtualGpu => 2,
-                PhysicalDeviceType::Cpu => 3,
-    
-                // Note that there exists `PhysicalDeviceType::Other`, however,
-                // `PhysicalDeviceType` is a non-exhaustive enum. Thus, one should
-                // match wildcard `_` to catch all unknown device types.
-                _ => 4,
+            .min_by_key(|(p, _)| {
+                let type_rank = match p.properties().device_type {
+                    PhysicalDeviceType::DiscreteGpu => 0,
+                    PhysicalDeviceType::IntegratedGpu => 1,
+                    PhysicalDeviceType::VirtualGpu => 2,
+                    PhysicalDeviceType::Cpu => 3,
+
+                    // Note that there exists `PhysicalDeviceType::Other`, however,
+                    // `PhysicalDeviceType` is a non-exhaustive enum. Thus, one should
+                    // match wildcard `_` to catch all unknown device types.
+                    _ => 4,
+                };
+                // Break ties between devices of the same type by preferring
+                // the one with the largest device-local memory heap.
+                let max_heap_size = p.memory_properties().memory_heaps.iter()
+                    .filter(|heap| heap.flags.contains(vulkano::memory::MemoryHeapFlags::DEVICE_LOCAL))
+                    .map(|heap| heap.size)
+                    .max()
+                    .unwrap_or(0);
+                (type_rank, std::cmp::Reverse(max_heap_size))
             })
             .expect("no device available")
     }
@@ -65,9 +86,15 @@ impl DeviceCtx {
         let surface = vulkano_win::create_surface_from_winit(window, instance.clone())?;
         let device_extensions = DeviceExtensions {
             khr_swapchain: true,
-            ..DeviceExtensions::empty()
+            ..RAY_TRACING_EXTENSIONS
         };
         let (physical_device, queue_family_index) = Self::select_physical_device(&instance, &surface, &device_extensions);
+        let device_features = DeviceFeatures {
+            acceleration_structure: true,
+            ray_tracing_pipeline: true,
+            buffer_device_address: true,
+            ..DeviceFeatures::empty()
+        };
         let (device, mut queues) = Device::new(
             physical_device.clone(),
             DeviceCreateInfo {
@@ -76,6 +103,7 @@ impl DeviceCtx {
                     ..Default::default()
                 }],
                 enabled_extensions: device_extensions,
+                enabled_features: device_features,
                 ..Default::default()
             },
         ).expect("failed to create device");
@@ -90,12 +118,36 @@ impl DeviceCtx {
     }
 }
 
+/// How many frames may be recorded and submitted before the CPU must wait
+/// for an earlier one to finish on the GPU. Deliberately independent of the
+/// swapchain's image count, unlike the one-fence-per-image scheme this
+/// replaces.
+const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
 pub struct PresentCtx {
     swapchain: Arc<Swapchain>,
     swapchain_images: Vec<Arc<SwapchainImage>>,
     swapchain_dimensions: PhysicalSize<u32>,
-    swapchain_fences: Vec<Option<Arc<FenceSignalFuture<Box<dyn GpuFuture>>>>>,
-    last_image_index: u32,
+
+    /// Single timeline semaphore shared by every frame slot, signalled with a
+    /// monotonically increasing value on each submission. Frame slots and
+    /// swapchain images alike are tracked as "reached value N on this one
+    /// timeline", so any two values are always comparable, unlike per-slot
+    /// timelines whose counters are unrelated to each other.
+    timeline_semaphore: Arc<Semaphore>,
+    next_timeline_value: u64,
+    /// Timeline value that must be reached before a given frame slot's
+    /// resources (command buffer allocations, etc.) may be reused. `0` until
+    /// the slot has submitted once.
+    frame_timeline_values: Vec<u64>,
+    /// Timeline value that must be reached before a given swapchain image may
+    /// be recorded into again, i.e. the value of whichever frame slot last
+    /// submitted work against it. `None` until the image has been used once.
+    images_in_flight: Vec<Option<u64>>,
+    current_frame: usize,
+
+    memory_allocator: StandardMemoryAllocator,
+    depth_image_view: Arc<ImageView>,
 
     render_pass: Arc<RenderPass>,
     framebuffers: Vec<Arc<Framebuffer>>,
@@ -112,6 +164,12 @@ pub struct RenderStatus {
 }
 
 impl PresentCtx {
+    /// Format used for the depth attachment. `D16_UNORM` is guaranteed by
+    /// the Vulkan spec to support `DEPTH_STENCIL_ATTACHMENT`, so unlike the
+    /// ash-based `RenderPassCtx` this doesn't need to query the physical
+    /// device for a supported candidate.
+    const DEPTH_FORMAT: Format = Format::D16_UNORM;
+
     fn get_render_pass(
         device: Arc<Device>,
         swapchain: Arc<Swapchain>
@@ -125,16 +183,42 @@ impl PresentCtx {
                     format: swapchain.image_format(), // set the format the same as the swapchain
                     samples: 1,
                 },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Self::DEPTH_FORMAT,
+                    samples: 1,
+                },
             },
             pass: {
                 color: [color],
-                depth_stencil: {},
+                depth_stencil: {depth},
             },
         ).unwrap()
     }
-    
+
+    fn get_depth_image_view(
+        memory_allocator: &StandardMemoryAllocator,
+        dimensions: PhysicalSize<u32>,
+    ) -> Arc<ImageView> {
+        let depth_image = Image::new(
+            memory_allocator,
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: Self::DEPTH_FORMAT,
+                extent: [dimensions.width, dimensions.height, 1],
+                usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap();
+        ImageView::new_default(depth_image).unwrap()
+    }
+
     fn get_framebuffers(
         images: &[Arc<SwapchainImage>],
+        depth_image_view: Arc<ImageView>,
         render_pass: Arc<RenderPass>,
     ) -> Vec<Arc<Framebuffer>> {
         images.iter().map(|image| {
@@ -142,7 +226,7 @@ impl PresentCtx {
             Framebuffer::new(
                 render_pass.clone(),
                 FramebufferCreateInfo {
-                    attachments: vec![view],
+                    attachments: vec![view, depth_image_view.clone()],
                     ..Default::default()
                 },
             )
@@ -163,6 +247,7 @@ impl PresentCtx {
             .input_assembly_state(InputAssemblyState::new())
             .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
             .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
             .render_pass(Subpass::from(render_pass, 0).unwrap())
             .build(device)
             .unwrap()
@@ -224,10 +309,22 @@ impl PresentCtx {
                 ..Default::default()
             }
         )?;
-        let swapchain_fences = vec![None; swapchain_images.len()];
+        let timeline_semaphore = Semaphore::new(
+            device_ctx.device.clone(),
+            SemaphoreCreateInfo {
+                semaphore_type: SemaphoreType::Timeline,
+                initial_value: 0,
+                ..Default::default()
+            },
+        )?;
+        let frame_timeline_values = vec![0; MAX_FRAMES_IN_FLIGHT];
+        let images_in_flight = vec![None; swapchain_images.len()];
+
+        let memory_allocator = StandardMemoryAllocator::new_default(device_ctx.device.clone());
+        let depth_image_view = Self::get_depth_image_view(&memory_allocator, dimensions);
 
         let render_pass = Self::get_render_pass(device_ctx.device.clone(), swapchain.clone());
-        let framebuffers = Self::get_framebuffers(&swapchain_images, render_pass.clone());
+        let framebuffers = Self::get_framebuffers(&swapchain_images, depth_image_view.clone(), render_pass.clone());
 
         let viewport = Viewport {
             origin: [0.0, 0.0],
@@ -250,8 +347,13 @@ impl PresentCtx {
             swapchain,
             swapchain_images,
             swapchain_dimensions: dimensions,
-            swapchain_fences,
-            last_image_index: 0,
+            timeline_semaphore,
+            next_timeline_value: 1,
+            frame_timeline_values,
+            images_in_flight,
+            current_frame: 0,
+            memory_allocator,
+            depth_image_view,
             render_pass,
             framebuffers,
             viewport,
@@ -273,9 +375,13 @@ impl PresentCtx {
             image_extent: new_dimensions.into(),
             ..self.swapchain.create_info()
         })?;
-        let new_framebuffers = Self::get_framebuffers(&new_images, self.render_pass.clone());
-        
+        if is_resize {
+            self.depth_image_view = Self::get_depth_image_view(&self.memory_allocator, new_dimensions);
+        }
+        let new_framebuffers = Self::get_framebuffers(&new_images, self.depth_image_view.clone(), self.render_pass.clone());
+
         self.swapchain = new_swapchain;
+        self.images_in_flight = vec![None; new_images.len()];
         self.swapchain_images = new_images;
         self.swapchain_dimensions = new_dimensions;
         self.framebuffers = new_framebuffers;
@@ -304,6 +410,20 @@ impl PresentCtx {
             needs_recreate: false
         };
 
+        // Wait for this frame slot's previous submission to finish before
+        // reusing its resources (command buffer allocations, the semaphore
+        // itself) for a new one.
+        let frame_wait_value = self.frame_timeline_values[self.current_frame];
+        if frame_wait_value > 0 {
+            device_ctx.device.wait_semaphores(
+                &SemaphoreWaitInfo::semaphore_and_value(
+                    self.timeline_semaphore.clone(),
+                    frame_wait_value,
+                ),
+                None,
+            )?;
+        }
+
         let (next_image_index, suboptimal, acquire_future) = match vulkano::swapchain::acquire_next_image(self.swapchain.clone(), None) {
             Ok(r) => r,
             Err(AcquireError::OutOfDate) => return Ok(RenderStatus { rendered: false, needs_recreate: true }),
@@ -312,42 +432,399 @@ impl PresentCtx {
         if suboptimal {
             return_status.needs_recreate = true;
         }
-        if let Some(image_fence) = &self.swapchain_fences[next_image_index as usize] {
-            image_fence.wait(None).unwrap();
+
+        // MAX_FRAMES_IN_FLIGHT may be smaller or larger than the swapchain's
+        // image count, so a freshly acquired image can still be in flight
+        // under a different frame slot than the one we just waited on above.
+        if let Some(image_wait_value) = self.images_in_flight[next_image_index as usize] {
+            device_ctx.device.wait_semaphores(
+                &SemaphoreWaitInfo::semaphore_and_value(
+                    self.timeline_semaphore.clone(),
+                    image_wait_value,
+                ),
+                None,
+            )?;
         }
+
         let command_buffer = scene.build_command_buffer(&self.command_buffer_allocator, &device_ctx.queue, &self.pipeline, &self.framebuffers[next_image_index as usize]);
-        let future = match self.swapchain_fences[self.last_image_index as usize].clone() {
-            None => {
-                let mut now = sync::now(device_ctx.device.clone());
-                now.cleanup_finished();
-                now.boxed()
-            }
-            Some(fence) => fence.boxed(),
-        };
 
-        let future = future
+        let signal_value = self.next_timeline_value;
+        let timeline_semaphore = self.timeline_semaphore.clone();
+
+        let mut now = sync::now(device_ctx.device.clone());
+        now.cleanup_finished();
+
+        let future = now
             .join(acquire_future)
             .then_execute(device_ctx.queue.clone(), command_buffer)
             .unwrap()
+            .then_signal_semaphore(timeline_semaphore.clone(), signal_value)
             .then_swapchain_present(
                 device_ctx.queue.clone(),
                 SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), next_image_index),
-            );
+            )
+            .flush();
 
-        let future = (Box::new(future) as Box<dyn GpuFuture>).then_signal_fence_and_flush();
-        self.swapchain_fences[next_image_index as usize] = match future {
-            Ok(value) => Some(Arc::new(value)),
+        match future {
+            Ok(()) => {
+                self.frame_timeline_values[self.current_frame] = signal_value;
+                self.images_in_flight[next_image_index as usize] = Some(signal_value);
+                self.next_timeline_value += 1;
+            }
             Err(FlushError::OutOfDate) => {
                 return_status.needs_recreate = true;
-                None
             }
             Err(e) => {
                 println!("failed to flush future: {e}");
-                None
             }
-        };
-        self.last_image_index = next_image_index;
+        }
+
+        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
 
         Ok(return_status)
     }
-}
\ No newline at end of file
+}
+
+/// Device-local vertex/index buffers for a `Scene`, uploaded once via a
+/// temporary host-visible staging buffer rather than written to directly
+/// (device-local memory is rarely host-visible, and even when it is,
+/// reading from it on the CPU's upload path is far slower than a GPU-side
+/// copy).
+pub struct SceneBuffers {
+    pub vertex_buffer: Subbuffer<[GeoVertex]>,
+    pub index_buffer: Subbuffer<[u32]>
+}
+
+impl DeviceCtx {
+    /// Stages `scene`'s vertex and index data through a host-visible buffer
+    /// and copies it into device-local buffers, then waits for the copy to
+    /// complete before returning.
+    pub fn create_scene_buffers(&self, scene: &Scene) -> Result<SceneBuffers> {
+        let memory_allocator = StandardMemoryAllocator::new_default(self.device.clone());
+        let command_buffer_allocator = StandardCommandBufferAllocator::new(self.device.clone(), Default::default());
+
+        let staging_vertex_buffer = Buffer::from_iter(
+            memory_allocator.clone().into(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            scene.vertices.iter().copied(),
+        )?;
+        let vertex_buffer = Buffer::new_slice::<GeoVertex>(
+            memory_allocator.clone().into(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST | BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            staging_vertex_buffer.len(),
+        )?;
+
+        let staging_index_buffer = Buffer::from_iter(
+            memory_allocator.clone().into(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            scene.indices.iter().copied(),
+        )?;
+        let index_buffer = Buffer::new_slice::<u32>(
+            memory_allocator.into(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST | BufferUsage::INDEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            staging_index_buffer.len(),
+        )?;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(staging_vertex_buffer, vertex_buffer.clone()))?
+            .copy_buffer(CopyBufferInfo::buffers(staging_index_buffer, index_buffer.clone()))?;
+        builder.build()?.execute(self.queue.clone())?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+
+        Ok(SceneBuffers { vertex_buffer, index_buffer })
+    }
+}
+
+pub struct RayTracingCtx {
+    device_ctx: Arc<Device>,
+    queue: Arc<Queue>,
+
+    blas_buffer: Subbuffer<[u8]>,
+    pub blas: Arc<AccelerationStructure>,
+    tlas_buffer: Subbuffer<[u8]>,
+    pub tlas: Arc<AccelerationStructure>,
+
+    pub pipeline: Arc<RayTracingPipeline>,
+    pub shader_binding_table: ShaderBindingTable,
+
+    pub output_image: Arc<Image>
+}
+
+impl RayTracingCtx {
+    /// Builds the (buffer, device-local acceleration structure) pair sized
+    /// for `geometries`/`primitive_count`, calling
+    /// `acceleration_structure_build_sizes` to size both the AS buffer
+    /// and the scratch buffer.
+    fn build_acceleration_structure(
+        device_ctx: &DeviceCtx,
+        ty: AccelerationStructureType,
+        geometries: AccelerationStructureGeometries,
+        primitive_count: u32,
+    ) -> Result<(Subbuffer<[u8]>, Arc<AccelerationStructure>)> {
+        let allocator = StandardMemoryAllocator::new_default(device_ctx.device.clone());
+
+        let mut build_info = AccelerationStructureBuildGeometryInfo {
+            flags: BuildAccelerationStructureFlags::PREFER_FAST_TRACE,
+            mode: BuildAccelerationStructureMode::Build,
+            ..AccelerationStructureBuildGeometryInfo::new(geometries)
+        };
+        let build_sizes = device_ctx.device.acceleration_structure_build_sizes(
+            ty,
+            &build_info,
+            &[primitive_count],
+        )?;
+
+        let as_buffer = Buffer::new_slice::<u8>(
+            allocator.clone().into(),
+            BufferCreateInfo {
+                usage: BufferUsage::ACCELERATION_STRUCTURE_STORAGE | BufferUsage::SHADER_DEVICE_ADDRESS,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            build_sizes.acceleration_structure_size,
+        )?;
+        let acceleration_structure = unsafe {
+            AccelerationStructure::new(
+                device_ctx.device.clone(),
+                AccelerationStructureCreateInfo {
+                    ty,
+                    ..AccelerationStructureCreateInfo::new(as_buffer.clone())
+                },
+            )?
+        };
+
+        let scratch_buffer = Buffer::new_slice::<u8>(
+            allocator.into(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::SHADER_DEVICE_ADDRESS,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            build_sizes.build_scratch_size,
+        )?;
+
+        build_info.dst_acceleration_structure = Some(acceleration_structure.clone());
+        build_info.scratch_data = Some(scratch_buffer);
+
+        let command_buffer_allocator = StandardCommandBufferAllocator::new(device_ctx.device.clone(), Default::default());
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            device_ctx.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        unsafe {
+            builder.build_acceleration_structure(
+                build_info,
+                std::iter::once(AccelerationStructureBuildRangeInfo {
+                    primitive_count,
+                    primitive_offset: 0,
+                    first_vertex: 0,
+                    transform_offset: 0,
+                }).collect(),
+            )?;
+        }
+        builder.build()?.execute(device_ctx.queue.clone())?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+
+        Ok((as_buffer, acceleration_structure))
+    }
+
+    /// Builds a BLAS from `scene.vertices` and a TLAS holding a single
+    /// instance of it, then a ray tracing pipeline (raygen/miss/closest-hit)
+    /// and its shader binding table, plus an `output_image` storage image
+    /// sized to `output_extent`.
+    pub fn new(
+        device_ctx: &DeviceCtx,
+        scene: &Scene,
+        model_transform: [[f32; 4]; 3],
+        raygen: Arc<ShaderModule>,
+        miss: Arc<ShaderModule>,
+        closest_hit: Arc<ShaderModule>,
+        output_extent: [u32; 2],
+    ) -> Result<Self> {
+        let allocator = StandardMemoryAllocator::new_default(device_ctx.device.clone());
+
+        let vertex_buffer = Buffer::from_iter(
+            allocator.clone().into(),
+            BufferCreateInfo {
+                usage: BufferUsage::SHADER_DEVICE_ADDRESS
+                    | BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            scene.vertices.iter().copied(),
+        )?;
+        let vertex_count = scene.vertices.len() as u32;
+        let triangle_count = vertex_count / 3;
+
+        let triangles_data = AccelerationStructureGeometryTrianglesData {
+            vertex_data: Some(vertex_buffer.into_bytes()),
+            vertex_stride: std::mem::size_of::<GeoVertex>() as u32,
+            vertex_format: Format::R32G32B32_SFLOAT,
+            max_vertex: vertex_count.saturating_sub(1),
+            ..AccelerationStructureGeometryTrianglesData::new(
+                GeometryFlags::OPAQUE,
+            )
+        };
+        let (blas_buffer, blas) = Self::build_acceleration_structure(
+            device_ctx,
+            AccelerationStructureType::BottomLevel,
+            AccelerationStructureGeometries::Triangles(vec![triangles_data]),
+            triangle_count,
+        )?;
+
+        let instance = AccelerationStructureInstance {
+            transform: model_transform,
+            instance_custom_index_and_mask: 0xFF_00_00_00,
+            instance_shader_binding_table_record_offset_and_flags: 0,
+            acceleration_structure_reference: blas.device_address().get(),
+        };
+        let instances_buffer = Buffer::from_iter(
+            allocator.into(),
+            BufferCreateInfo {
+                usage: BufferUsage::SHADER_DEVICE_ADDRESS
+                    | BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            [instance],
+        )?;
+        let instances_data = AccelerationStructureGeometryInstancesData::new(
+            AccelerationStructureGeometryInstancesDataType::Values(Some(instances_buffer)),
+        );
+        let (tlas_buffer, tlas) = Self::build_acceleration_structure(
+            device_ctx,
+            AccelerationStructureType::TopLevel,
+            AccelerationStructureGeometries::Instances(instances_data),
+            1,
+        )?;
+
+        let stages = [
+            PipelineShaderStageCreateInfo::new(ShaderModule::entry_point(&raygen, "main").unwrap()),
+            PipelineShaderStageCreateInfo::new(ShaderModule::entry_point(&miss, "main").unwrap()),
+            PipelineShaderStageCreateInfo::new(ShaderModule::entry_point(&closest_hit, "main").unwrap()),
+        ];
+        let groups = [
+            RayTracingShaderGroupCreateInfo::General { general_shader: 0 },
+            RayTracingShaderGroupCreateInfo::General { general_shader: 1 },
+            RayTracingShaderGroupCreateInfo::TrianglesHit {
+                closest_hit_shader: Some(2),
+                any_hit_shader: None,
+            },
+        ];
+        let layout = PipelineLayout::new(
+            device_ctx.device.clone(),
+            PipelineLayout::new_default_pipeline_layout_create_info(stages.iter()).into_pipeline_layout_create_info(device_ctx.device.clone())?,
+        )?;
+        let pipeline = RayTracingPipeline::new(
+            device_ctx.device.clone(),
+            None,
+            RayTracingPipelineCreateInfo {
+                max_pipeline_ray_recursion_depth: 1,
+                groups: groups.into_iter().collect(),
+                ..RayTracingPipelineCreateInfo::layout(stages.into_iter().collect(), layout)
+            },
+        )?;
+
+        // Shader binding table entries are aligned to
+        // `shader_group_handle_alignment`/`shader_group_base_alignment`
+        // from `PhysicalDeviceRayTracingPipelineProperties`.
+        let shader_binding_table = ShaderBindingTable::new(
+            StandardMemoryAllocator::new_default(device_ctx.device.clone()).into(),
+            &pipeline,
+        )?;
+
+        let output_image = Image::new(
+            StandardMemoryAllocator::new_default(device_ctx.device.clone()).into(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: Format::R32G32B32A32_SFLOAT,
+                extent: [output_extent[0], output_extent[1], 1],
+                usage: ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )?;
+
+        Ok(Self {
+            device_ctx: device_ctx.device.clone(),
+            queue: device_ctx.queue.clone(),
+            blas_buffer,
+            blas,
+            tlas_buffer,
+            tlas,
+            pipeline,
+            shader_binding_table,
+            output_image,
+        })
+    }
+
+    /// Binds the ray tracing pipeline and dispatches `trace_rays` over
+    /// `extent`. No descriptor sets or push constants are bound here, so
+    /// this function binds neither `tlas` nor `output_image` to the pipeline
+    /// and passes no camera `proj`/`view` to the raygen shader.
+    /// `PresentCtx::render` does not blit `output_image` into the swapchain
+    /// image.
+    pub fn trace_rays(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        extent: [u32; 2],
+    ) -> Result<()> {
+        unsafe {
+            builder
+                .bind_pipeline_ray_tracing(self.pipeline.clone())?
+                .trace_rays(
+                    self.shader_binding_table.addresses().clone(),
+                    [extent[0], extent[1], 1],
+                )?;
+        }
+        Ok(())
+    }
+}
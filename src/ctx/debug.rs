@@ -1,7 +1,7 @@
-use std::{ffi::{c_void, CStr}, rc::Rc};
+use std::{ffi::{c_void, CStr, CString}, rc::Rc};
 
 use anyhow::{Result, bail};
-use ash::{extensions::ext::DebugUtils, vk::{DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, Bool32, DebugUtilsMessengerCallbackDataEXT, self, DebugUtilsMessengerEXT},};
+use ash::{extensions::ext::DebugUtils, vk::{DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, Bool32, DebugUtilsMessengerCallbackDataEXT, self, DebugUtilsMessengerEXT, DebugUtilsObjectNameInfoEXT, DebugUtilsLabelEXT, CommandBuffer, Handle},};
 
 use super::instance::InstanceCtx;
 
@@ -15,9 +15,13 @@ pub fn required_extension_names(with_validation: bool) -> Vec<*const i8> {
     }
 }
 
-unsafe extern "system" fn vk_message_callback(
+/// Routes validation output through the `log` crate. Also installed via
+/// `pNext` on `VkInstanceCreateInfo` itself (see `InstanceCtx::new`), so
+/// messages from instance creation/destruction are covered, not just the
+/// runtime messenger created afterwards.
+pub(crate) unsafe extern "system" fn vk_message_callback(
     message_severity: DebugUtilsMessageSeverityFlagsEXT,
-    _message_types: DebugUtilsMessageTypeFlagsEXT,
+    message_types: DebugUtilsMessageTypeFlagsEXT,
     callback_data: *const DebugUtilsMessengerCallbackDataEXT,
     _user_data: *mut c_void,
 ) -> Bool32 {
@@ -27,6 +31,7 @@ unsafe extern "system" fn vk_message_callback(
     } else {
         "(vkw: unknown)".to_string()
     };
+    let type_str = MessageType::try_from(message_types).map(|x| x.to_string()).unwrap_or_else(|_| "(vkw: unknown)".to_string());
     let message = if let Some(callback_data) = callback_data.as_ref() {
         CStr::from_ptr(callback_data.p_message).to_str().unwrap_or("(vkw: could not read p_message)")
     } else {
@@ -34,12 +39,12 @@ unsafe extern "system" fn vk_message_callback(
     };
 
     match message_severity.unwrap_or(MessageSeverity::Warning) {
-        MessageSeverity::Error => log::error!("[VK/{}] {}", severity_str, message),
-        MessageSeverity::Warning => log::warn!("[VK/{}] {}", severity_str, message),
-        MessageSeverity::Info => log::info!("[VK/{}] {}", severity_str, message),
-        MessageSeverity::Verbose => log::debug!("[VK/{}] {}", severity_str, message),
+        MessageSeverity::Error => log::error!("[VK/{}/{}] {}", severity_str, type_str, message),
+        MessageSeverity::Warning => log::warn!("[VK/{}/{}] {}", severity_str, type_str, message),
+        MessageSeverity::Info => log::debug!("[VK/{}/{}] {}", severity_str, type_str, message),
+        MessageSeverity::Verbose => log::trace!("[VK/{}/{}] {}", severity_str, type_str, message),
     }
-    
+
     vk::FALSE
 }
 pub struct DebugCtx {
@@ -70,6 +75,63 @@ impl DebugCtx {
     }
 }
 
+impl DebugCtx {
+    /// Labels a Vulkan handle with a human-readable name so RenderDoc and
+    /// validation output stop referring to it as a bare handle.
+    pub fn set_object_name<T: Handle>(
+        &self,
+        handle: T,
+        name: &str
+    ) -> Result<()> {
+        let name_c = CString::new(name)?;
+        let name_info = DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(T::TYPE)
+            .object_handle(handle.as_raw())
+            .object_name(&name_c);
+        unsafe { self.debug_utils.set_debug_utils_object_name(&name_info)? };
+        Ok(())
+    }
+
+    /// Opens a named, coloured label range on a command buffer. Must be
+    /// matched with `end_label`.
+    pub fn begin_label(
+        &self,
+        command_buffer: CommandBuffer,
+        name: &str,
+        color: [f32; 4]
+    ) -> Result<()> {
+        let name_c = CString::new(name)?;
+        let label_info = DebugUtilsLabelEXT::builder()
+            .label_name(&name_c)
+            .color(color);
+        unsafe { self.debug_utils.cmd_begin_debug_utils_label(command_buffer, &label_info) };
+        Ok(())
+    }
+
+    pub fn end_label(
+        &self,
+        command_buffer: CommandBuffer
+    ) {
+        unsafe { self.debug_utils.cmd_end_debug_utils_label(command_buffer) };
+    }
+
+    /// Inserts a single named, coloured marker into a command buffer without
+    /// opening a range.
+    pub fn insert_label(
+        &self,
+        command_buffer: CommandBuffer,
+        name: &str,
+        color: [f32; 4]
+    ) -> Result<()> {
+        let name_c = CString::new(name)?;
+        let label_info = DebugUtilsLabelEXT::builder()
+            .label_name(&name_c)
+            .color(color);
+        unsafe { self.debug_utils.cmd_insert_debug_utils_label(command_buffer, &label_info) };
+        Ok(())
+    }
+}
+
 impl Drop for DebugCtx {
     fn drop(&mut self) {
         unsafe {
@@ -1,59 +1,206 @@
-use std::{ffi::{CStr, c_char, CString}, rc::Rc};
+use std::{cell::RefCell, ffi::{CStr, c_char, CString}, rc::Rc};
 use anyhow::Result;
-use ash::{vk::{self, PhysicalDevice, Queue}, Device, extensions::khr::Swapchain};
+use ash::{vk::{self, PhysicalDevice, Queue}, Device, extensions::{khr::{self, Swapchain}, ext::DebugUtils}};
+use gpu_allocator::{
+    vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator, AllocatorCreateDesc},
+    MemoryLocation
+};
 
 use super::surface::{SurfaceCtx, SwapchainSupportDetails};
 
 pub const REQUIRED_DEVICE_EXT: &[&CStr] = &[Swapchain::name()];
 
+/// Extension-gated device features a caller can opt into; `select_physical_device`
+/// rejects any device that doesn't advertise the ones requested here.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestedDeviceFeatures {
+    pub ray_tracing: bool,
+    pub dynamic_rendering: bool
+}
+
+/// `REQUIRED_DEVICE_EXT` plus whatever `requested` needs: `VK_KHR_acceleration_structure`,
+/// `VK_KHR_ray_tracing_pipeline`, `VK_KHR_deferred_host_operations` and
+/// `VK_KHR_buffer_device_address` for ray tracing, `VK_KHR_dynamic_rendering`
+/// for dynamic rendering.
+fn required_device_extensions(requested: RequestedDeviceFeatures) -> Vec<&'static CStr> {
+    let mut extensions = REQUIRED_DEVICE_EXT.to_vec();
+    if requested.ray_tracing {
+        extensions.extend([
+            khr::AccelerationStructure::name(),
+            khr::RayTracingPipeline::name(),
+            khr::DeferredHostOperations::name(),
+            khr::BufferDeviceAddress::name()
+        ]);
+    }
+    if requested.dynamic_rendering {
+        extensions.push(khr::DynamicRendering::name());
+    }
+    extensions
+}
+
+/// Lets a caller pin physical device selection instead of relying purely on
+/// `score_device`, e.g. to force the integrated GPU for a low-power profile.
+/// The override still has to pass the hard-requirement gate in
+/// `select_physical_device` — it can't resurrect an unsuitable device.
+#[derive(Debug, Clone)]
+pub enum PreferredDevice {
+    Name(String),
+    Type(vk::PhysicalDeviceType)
+}
+
 fn select_physical_device(
-    surface_ctx: &SurfaceCtx
+    surface_ctx: &SurfaceCtx,
+    requested: RequestedDeviceFeatures,
+    preferred: Option<&PreferredDevice>
 ) -> Result<PhysicalDeviceInfo> {
+    let required_extensions = required_device_extensions(requested);
     let devices = unsafe { surface_ctx.instance_ctx.instance.enumerate_physical_devices() }?;
     let devices_and_queues = devices.into_iter()
         .map(|device| Ok((device, find_queue_families(surface_ctx, device)?)))
         .collect::<Result<Vec<_>>>()?;
-    devices_and_queues.into_iter()
-    .filter_map(|(device, queues)| {
-        let (graphics_family_index, present_family_index) = queues?;
-        let supports_required_extensions = test_required_extensions(surface_ctx, device).ok()?;
-        if !supports_required_extensions { return None; }
-        let swapchain_support_details = surface_ctx.swapchain_support_details(device).ok()?;
-        let swapchain_is_adequate = !swapchain_support_details.formats.is_empty() && !swapchain_support_details.present_modes.is_empty();
-        if !swapchain_is_adequate { return None; }
-        let props = unsafe { surface_ctx.instance_ctx.instance.get_physical_device_properties(device) };
-        let debug_device_name = unsafe { CStr::from_ptr(props.device_name.as_ptr()) }.to_owned();
-        let dedup_family_indices = if graphics_family_index == present_family_index { vec![graphics_family_index] } else { vec![graphics_family_index, present_family_index] };
-        Some(PhysicalDeviceInfo {
-            device,
-            graphics_family_index,
-            present_family_index,
-            dedup_family_indices,
-            swapchain_support_details,
-            debug_device_name,
+    let candidates = devices_and_queues.into_iter()
+        .filter_map(|(device, queues)| {
+            let queue_families = queues?;
+            let supports_required_extensions = test_required_extensions(surface_ctx, device, &required_extensions).ok()?;
+            if !supports_required_extensions { return None; }
+            if !supports_requested_features(surface_ctx, device, requested) { return None; }
+            let swapchain_support_details = surface_ctx.swapchain_support_details(device).ok()?;
+            let swapchain_is_adequate = !swapchain_support_details.formats.is_empty() && !swapchain_support_details.present_modes.is_empty();
+            if !swapchain_is_adequate { return None; }
+            let props = unsafe { surface_ctx.instance_ctx.instance.get_physical_device_properties(device) };
+            let debug_device_name = unsafe { CStr::from_ptr(props.device_name.as_ptr()) }.to_owned();
+            let mut dedup_family_indices = vec![queue_families.graphics, queue_families.present, queue_families.transfer, queue_families.compute];
+            dedup_family_indices.sort_unstable();
+            dedup_family_indices.dedup();
+            let score = score_device(surface_ctx, device, queue_families.graphics, queue_families.present);
+            Some((PhysicalDeviceInfo {
+                device,
+                graphics_family_index: queue_families.graphics,
+                present_family_index: queue_families.present,
+                transfer_family_index: queue_families.transfer,
+                compute_family_index: queue_families.compute,
+                dedup_family_indices,
+                swapchain_support_details,
+                debug_device_name,
+            }, score))
         })
-    })
-    .next().ok_or(anyhow::anyhow!("No suitable physical device"))
+        .collect::<Vec<_>>();
+
+    if let Some(preferred) = preferred {
+        if let Some(index) = candidates.iter().position(|(info, _)| matches_preferred(surface_ctx, info, preferred)) {
+            let (info, score) = candidates.into_iter().nth(index).unwrap();
+            log::debug!("Selected preferred physical device: {:?} (score {})", info.debug_device_name, score);
+            return Ok(info);
+        }
+    }
+
+    let (info, score) = candidates.into_iter()
+        .max_by_key(|(_, score)| *score)
+        .ok_or(anyhow::anyhow!("No suitable physical device"))?;
+    log::debug!("Selected physical device: {:?} (score {})", info.debug_device_name, score);
+    Ok(info)
+}
+
+fn matches_preferred(
+    surface_ctx: &SurfaceCtx,
+    info: &PhysicalDeviceInfo,
+    preferred: &PreferredDevice
+) -> bool {
+    match preferred {
+        PreferredDevice::Name(name) => info.debug_device_name.to_str().map(|found| found == name).unwrap_or(false),
+        PreferredDevice::Type(device_type) => {
+            let props = unsafe { surface_ctx.instance_ctx.instance.get_physical_device_properties(info.device) };
+            props.device_type == *device_type
+        }
+    }
+}
+
+/// Gives discrete GPUs a larger type bonus than integrated ones, adds the
+/// size (in MiB) of the largest `DEVICE_LOCAL` memory heap, and gives a small
+/// bonus when graphics and present share a queue family (one fewer ownership
+/// transfer per frame). The heap term is not capped, so a device with a much
+/// larger heap can outscore one of a better type.
+fn score_device(
+    surface_ctx: &SurfaceCtx,
+    device: PhysicalDevice,
+    graphics_family_index: u32,
+    present_family_index: u32
+) -> i64 {
+    let props = unsafe { surface_ctx.instance_ctx.instance.get_physical_device_properties(device) };
+    let type_bonus = match props.device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 10_000,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 1_000,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 100,
+        _ => 0
+    };
+
+    let memory_props = unsafe { surface_ctx.instance_ctx.instance.get_physical_device_memory_properties(device) };
+    let max_heap_mib = memory_props.memory_heaps[..memory_props.memory_heap_count as usize].iter()
+        .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|heap| heap.size / (1024 * 1024))
+        .max()
+        .unwrap_or(0) as i64;
+
+    let same_family_bonus = if graphics_family_index == present_family_index { 100 } else { 0 };
+
+    type_bonus + max_heap_mib + same_family_bonus
 }
 
 fn test_required_extensions(
     surface_ctx: &SurfaceCtx,
-    device: PhysicalDevice
+    device: PhysicalDevice,
+    required_extensions: &[&CStr]
 ) -> Result<bool> {
     let extension_props = unsafe { surface_ctx.instance_ctx.instance.enumerate_device_extension_properties(device)? };
     let extension_names = extension_props.iter()
         .map(|x| unsafe { CStr::from_ptr(x.extension_name.as_ptr()) })
         .collect::<Vec<_>>();
-    let has_all_extensions = REQUIRED_DEVICE_EXT.iter().all(|x| extension_names.contains(x));
+    let has_all_extensions = required_extensions.iter().all(|x| extension_names.contains(x));
     Ok(has_all_extensions)
 }
 
+/// Checks `requested`'s features via a `PhysicalDeviceFeatures2` pNext chain;
+/// a feature that wasn't requested is never the reason a device gets rejected.
+fn supports_requested_features(
+    surface_ctx: &SurfaceCtx,
+    device: PhysicalDevice,
+    requested: RequestedDeviceFeatures
+) -> bool {
+    let mut acceleration_structure_features = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::builder().build();
+    let mut ray_tracing_pipeline_features = vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::builder().build();
+    let mut buffer_device_address_features = vk::PhysicalDeviceBufferDeviceAddressFeatures::builder().build();
+    let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::builder().build();
+    let mut features2 = vk::PhysicalDeviceFeatures2::builder()
+        .push_next(&mut acceleration_structure_features)
+        .push_next(&mut ray_tracing_pipeline_features)
+        .push_next(&mut buffer_device_address_features)
+        .push_next(&mut dynamic_rendering_features)
+        .build();
+    unsafe { surface_ctx.instance_ctx.instance.get_physical_device_features2(device, &mut features2) };
+
+    (!requested.ray_tracing || (
+        acceleration_structure_features.acceleration_structure == vk::TRUE
+            && ray_tracing_pipeline_features.ray_tracing_pipeline == vk::TRUE
+            && buffer_device_address_features.buffer_device_address == vk::TRUE
+    )) && (!requested.dynamic_rendering || dynamic_rendering_features.dynamic_rendering == vk::TRUE)
+}
+
+/// Finds a graphics family, a present-capable family, and (where the
+/// hardware exposes them) a dedicated transfer family (`TRANSFER` without
+/// `GRAPHICS`) and a dedicated async-compute family (`COMPUTE` without
+/// `GRAPHICS`). Falls back to the graphics family for either when no
+/// dedicated one exists, so callers can always submit to `transfer`/`compute`
+/// without checking for a fallback themselves. Scans every family rather
+/// than stopping at the first graphics+present match, since a dedicated
+/// transfer/compute family is often a later entry in the list.
 fn find_queue_families(
     surface_ctx: &SurfaceCtx,
     device: PhysicalDevice
-) -> Result<Option<(u32, u32)>> {
+) -> Result<Option<QueueFamilyIndices>> {
     let mut graphics = None;
     let mut present = None;
+    let mut transfer = None;
+    let mut compute = None;
     let props = unsafe { surface_ctx.instance_ctx.instance.get_physical_device_queue_family_properties(device) };
     for (index, family) in props.iter().filter(|f| f.queue_count > 0).enumerate() {
         let index = index as u32;
@@ -64,19 +211,26 @@ fn find_queue_families(
         if present_support && present.is_none() {
             present = Some(index);
         }
-        if let Some(graphics) = graphics {
-            if let Some(present) = present {
-                return Ok(Some((graphics, present)))
-            }
+        if family.queue_flags.contains(vk::QueueFlags::TRANSFER) && !family.queue_flags.contains(vk::QueueFlags::GRAPHICS) && transfer.is_none() {
+            transfer = Some(index);
+        }
+        if family.queue_flags.contains(vk::QueueFlags::COMPUTE) && !family.queue_flags.contains(vk::QueueFlags::GRAPHICS) && compute.is_none() {
+            compute = Some(index);
         }
     }
-    Ok(None)
+    Ok(graphics.zip(present).map(|(graphics, present)| QueueFamilyIndices {
+        graphics,
+        present,
+        transfer: transfer.unwrap_or(graphics),
+        compute: compute.unwrap_or(graphics)
+    }))
 }
 
 fn create_logical_device(
     surface_ctx: &SurfaceCtx,
     physical_info: &PhysicalDeviceInfo,
-    layer_name_pointers: &[*const c_char]
+    layer_name_pointers: &[*const c_char],
+    requested: RequestedDeviceFeatures
 ) -> Result<LogicalDeviceInfo> {
     let queue_priorities = [1.0f32];
     let queue_create_infos = physical_info.dedup_family_indices.iter()
@@ -85,47 +239,167 @@ fn create_logical_device(
             .queue_priorities(&queue_priorities)
             .build()
         ).collect::<Vec<_>>();
-    let device_extensions_ptrs = REQUIRED_DEVICE_EXT.iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
+    let required_extensions = required_device_extensions(requested);
+    let device_extensions_ptrs = required_extensions.iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
     let device_features = vk::PhysicalDeviceFeatures::builder().build();
-    let device_create_info = vk::DeviceCreateInfo::builder()
+
+    let mut acceleration_structure_features = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::builder()
+        .acceleration_structure(true)
+        .build();
+    let mut ray_tracing_pipeline_features = vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::builder()
+        .ray_tracing_pipeline(true)
+        .build();
+    let mut buffer_device_address_features = vk::PhysicalDeviceBufferDeviceAddressFeatures::builder()
+        .buffer_device_address(true)
+        .build();
+    let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::builder()
+        .dynamic_rendering(true)
+        .build();
+
+    let mut device_create_info_builder = vk::DeviceCreateInfo::builder()
         .queue_create_infos(&queue_create_infos)
         .enabled_extension_names(&device_extensions_ptrs)
         .enabled_features(&device_features)
-        .enabled_layer_names(layer_name_pointers)
-        .build();
+        .enabled_layer_names(layer_name_pointers);
+    if requested.ray_tracing {
+        device_create_info_builder = device_create_info_builder
+            .push_next(&mut acceleration_structure_features)
+            .push_next(&mut ray_tracing_pipeline_features)
+            .push_next(&mut buffer_device_address_features);
+    }
+    if requested.dynamic_rendering {
+        device_create_info_builder = device_create_info_builder.push_next(&mut dynamic_rendering_features);
+    }
+    let device_create_info = device_create_info_builder.build();
     let device = unsafe { surface_ctx.instance_ctx.instance.create_device(physical_info.device, &device_create_info, None)? };
     let graphics_queue = unsafe { device.get_device_queue(physical_info.graphics_family_index, 0) };
     let present_queue = unsafe { device.get_device_queue(physical_info.present_family_index, 0) };
+    let transfer_queue = unsafe { device.get_device_queue(physical_info.transfer_family_index, 0) };
+    let compute_queue = unsafe { device.get_device_queue(physical_info.compute_family_index, 0) };
     Ok(LogicalDeviceInfo {
         device,
         graphics_queue,
+        transfer_queue,
+        compute_queue,
         present_queue
     })
 }
 pub struct DeviceCtx {
     pub surface_ctx: Rc<SurfaceCtx>,
     pub physical_info: PhysicalDeviceInfo,
-    pub logical_info: LogicalDeviceInfo
+    pub logical_info: LogicalDeviceInfo,
+    /// `None` only once `Drop` has flushed it; every other observer sees `Some`.
+    allocator: RefCell<Option<Allocator>>
 }
 
 impl DeviceCtx {
     pub fn new(
-        surface_ctx: Rc<SurfaceCtx>
+        surface_ctx: Rc<SurfaceCtx>,
+        requested_features: RequestedDeviceFeatures,
+        preferred_device: Option<PreferredDevice>
     ) -> Result<Rc<DeviceCtx>> {
-        let physical_info = select_physical_device(&surface_ctx)?;
-        let logical_info = create_logical_device(&surface_ctx, &physical_info, &surface_ctx.instance_ctx.layer_name_pointers)?;
-        
+        let physical_info = select_physical_device(&surface_ctx, requested_features, preferred_device.as_ref())?;
+        let logical_info = create_logical_device(&surface_ctx, &physical_info, &surface_ctx.instance_ctx.layer_name_pointers, requested_features)?;
+
+        let allocator = Allocator::new(&AllocatorCreateDesc {
+            instance: surface_ctx.instance_ctx.instance.clone(),
+            device: logical_info.device.clone(),
+            physical_device: physical_info.device,
+            debug_settings: Default::default(),
+            buffer_device_address: requested_features.ray_tracing,
+            allocation_sizes: Default::default()
+        })?;
+
         log::debug!("DeviceCtx created ({})", physical_info.debug_device_name.to_str().unwrap_or("vkw: device is not nameable"));
         Ok(Rc::new(DeviceCtx {
             surface_ctx,
             physical_info,
-            logical_info
+            logical_info,
+            allocator: RefCell::new(Some(allocator))
         }))
     }
+
+    /// Suballocates device memory for `requirements` via `gpu-allocator`,
+    /// instead of every caller hand-rolling `vkAllocateMemory` and hitting
+    /// the platform's (often ~4096) allocation-count limit.
+    ///
+    /// `linear` must match the resource `requirements` was queried from:
+    /// `true` for buffers and linear-tiling images, `false` for
+    /// optimal-tiling images, so `gpu-allocator` can respect
+    /// buffer-image-granularity and avoid aliasing an adjacent resource of
+    /// the other kind within the same page.
+    pub fn allocate(
+        &self,
+        requirements: vk::MemoryRequirements,
+        location: MemoryLocation,
+        linear: bool,
+        name: &str
+    ) -> Result<Allocation> {
+        let mut allocator = self.allocator.borrow_mut();
+        let allocator = allocator.as_mut().expect("DeviceCtx::allocate called after the allocator was dropped");
+        Ok(allocator.allocate(&AllocationCreateDesc {
+            name,
+            requirements,
+            location,
+            linear,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged
+        })?)
+    }
+
+    pub fn free(
+        &self,
+        allocation: Allocation
+    ) -> Result<()> {
+        let mut allocator = self.allocator.borrow_mut();
+        let allocator = allocator.as_mut().expect("DeviceCtx::free called after the allocator was dropped");
+        allocator.free(allocation)?;
+        Ok(())
+    }
+
+    /// Gives a Vulkan object a debug name for RenderDoc captures and
+    /// validation output. Silently no-ops if `validation` wasn't requested
+    /// when the instance was created, since then there's no `VK_EXT_debug_utils`
+    /// function table to call into.
+    pub fn set_object_name<T: vk::Handle>(
+        &self,
+        handle: T,
+        name: &str
+    ) -> Result<()> {
+        let Some(debug_utils) = &self.surface_ctx.instance_ctx.debug_utils else {
+            return Ok(());
+        };
+        let name_bytes = name.as_bytes();
+        if name_bytes.len() < 64 {
+            let mut stack_buf = [0u8; 64];
+            stack_buf[..name_bytes.len()].copy_from_slice(name_bytes);
+            Self::set_object_name_raw(debug_utils, handle, CStr::from_bytes_until_nul(&stack_buf)?)
+        } else {
+            let mut heap_buf = Vec::with_capacity(name_bytes.len() + 1);
+            heap_buf.extend_from_slice(name_bytes);
+            heap_buf.push(0);
+            Self::set_object_name_raw(debug_utils, handle, CStr::from_bytes_until_nul(&heap_buf)?)
+        }
+    }
+
+    fn set_object_name_raw<T: vk::Handle>(
+        debug_utils: &DebugUtils,
+        handle: T,
+        name: &CStr
+    ) -> Result<()> {
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(T::TYPE)
+            .object_handle(handle.as_raw())
+            .object_name(name);
+        unsafe { debug_utils.set_debug_utils_object_name(&name_info)? };
+        Ok(())
+    }
 }
 
 impl Drop for DeviceCtx {
     fn drop(&mut self) {
+        // gpu-allocator needs the device alive to free its blocks, so flush
+        // it before destroying the device itself.
+        drop(self.allocator.borrow_mut().take());
         unsafe {
             self.logical_info.device.destroy_device(None);
         }
@@ -137,13 +411,34 @@ pub struct PhysicalDeviceInfo {
     pub device: PhysicalDevice,
     pub graphics_family_index: u32,
     pub present_family_index: u32,
+    /// A dedicated transfer family (`TRANSFER` without `GRAPHICS`) when the
+    /// hardware exposes one, otherwise `graphics_family_index`.
+    pub transfer_family_index: u32,
+    /// A dedicated async-compute family (`COMPUTE` without `GRAPHICS`) when
+    /// the hardware exposes one, otherwise `graphics_family_index`.
+    pub compute_family_index: u32,
     pub dedup_family_indices: Vec<u32>,
     pub swapchain_support_details: SwapchainSupportDetails,
     pub debug_device_name: CString
 }
 
+/// Queue family indices found by `find_queue_families`, before dedup.
+struct QueueFamilyIndices {
+    graphics: u32,
+    present: u32,
+    transfer: u32,
+    compute: u32
+}
+
 pub struct LogicalDeviceInfo {
     pub device: Device,
     pub graphics_queue: Queue,
-    pub present_queue: Queue
+    pub present_queue: Queue,
+    /// Overlaps with `graphics_queue` when the device has no dedicated
+    /// transfer family, so staging uploads can still be issued the same way.
+    pub transfer_queue: Queue,
+    /// Overlaps with `graphics_queue` when the device has no dedicated
+    /// async-compute family, so e.g. BLAS builds can still be issued the
+    /// same way.
+    pub compute_queue: Queue
 }
\ No newline at end of file
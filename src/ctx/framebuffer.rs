@@ -12,25 +12,51 @@ impl<'swp, 'dev, 'srf, 'ins, 'en> RenderPassCtx<'swp, 'dev, 'srf, 'ins, 'en> {
     pub fn create_framebuffer_ctx(
         &self,
     ) -> Result<FramebufferCtx> {
-        let framebuffers = self.swapchain_ctx.image_views.iter()
+        let framebuffers = Self::build_framebuffers(self.render_pass, self)?;
+
+        log::debug!("FramebufferCtx created");
+        Ok(FramebufferCtx {
+            render_pass_ctx: self,
+            framebuffers
+        })
+    }
+
+    fn build_framebuffers(
+        render_pass: ash::vk::RenderPass,
+        render_pass_ctx: &RenderPassCtx,
+    ) -> Result<Vec<Framebuffer>> {
+        render_pass_ctx.swapchain_ctx.image_views.iter()
             .map(|view| [*view])
             .map(|attachments| {
                 let framebuffer_info = FramebufferCreateInfo::builder()
-                    .render_pass(self.render_pass)
+                    .render_pass(render_pass)
                     .attachments(&attachments)
-                    .width(self.swapchain_ctx.swapchain_extent.width)
-                    .height(self.swapchain_ctx.swapchain_extent.height)
+                    .width(render_pass_ctx.swapchain_ctx.swapchain_extent.width)
+                    .height(render_pass_ctx.swapchain_ctx.swapchain_extent.height)
                     .layers(1)
                     .build();
-                Ok(unsafe { self.swapchain_ctx.device_ctx.logical_info.device.create_framebuffer(&framebuffer_info, None)? })
+                Ok(unsafe { render_pass_ctx.swapchain_ctx.device_ctx.logical_info.device.create_framebuffer(&framebuffer_info, None)? })
             })
-            .collect::<Result<Vec<_>>>()?;
+            .collect::<Result<Vec<_>>>()
+    }
+}
 
-        log::debug!("FramebufferCtx created");
-        Ok(FramebufferCtx {
-            render_pass_ctx: self,
-            framebuffers
-        })
+impl<'ren, 'swp, 'dev, 'srf, 'ins, 'en> FramebufferCtx<'ren, 'swp, 'dev, 'srf, 'ins, 'en> {
+    /// Tears down and rebuilds the framebuffers against the render pass's
+    /// current swapchain image views. Call this after `SwapchainCtx::recreate`
+    /// so the framebuffers pick up the new extent (and image views, which are
+    /// always replaced on recreate).
+    pub fn refresh(&mut self) -> Result<()> {
+        let device = &self.render_pass_ctx.swapchain_ctx.device_ctx.logical_info.device;
+        unsafe {
+            for framebuffer in &self.framebuffers {
+                device.destroy_framebuffer(*framebuffer, None);
+            }
+        }
+        self.framebuffers = RenderPassCtx::build_framebuffers(self.render_pass_ctx.render_pass, self.render_pass_ctx)?;
+
+        log::debug!("FramebufferCtx refreshed");
+        Ok(())
     }
 }
 
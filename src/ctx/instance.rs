@@ -1,6 +1,7 @@
 use std::{ffi::{CStr, CString, c_char}, sync::Arc};
 use anyhow::Result;
-use ash::{Instance, Entry, vk};
+use ash::{Instance, Entry, vk, extensions::ext::DebugUtils};
+use winit::window::Window;
 
 use crate::ctx::{surface, debug};
 
@@ -10,7 +11,11 @@ pub struct InstanceCtx {
     pub entry: Arc<Entry>,
     pub instance: Instance,
     pub layer_names: Vec<CString>,
-    pub layer_name_pointers: Vec<*const i8>
+    pub layer_name_pointers: Vec<*const i8>,
+    /// Loaded `VK_EXT_debug_utils` function table, if `validation` was
+    /// requested; `None` otherwise so callers can no-op debug naming/labels
+    /// without checking a separate flag.
+    pub debug_utils: Option<DebugUtils>
 }
 
 impl InstanceCtx {
@@ -18,27 +23,48 @@ impl InstanceCtx {
         entry: Arc<Entry>,
         app_info: AppInfo,
         user_extensions: &[&CStr],
-        validation: Option<(MessageSeverityFlags, MessageTypeFlags)>
+        validation: Option<(MessageSeverityFlags, MessageTypeFlags)>,
+        window: Option<&Window>
     ) -> Result<Self> {
         let (layer_names, layer_name_pointers) = Self::get_layer_names_and_pointers(validation.is_some())?;
         let app_info = app_info.try_into()?;
+        let surface_extensions = match window {
+            Some(window) => surface::required_extension_names(window)?,
+            None => Vec::new()
+        };
         let all_extensions = user_extensions.into_iter()
             .map(|x| x.as_ptr())
-            .chain(surface::required_extension_names_win32().into_iter())
+            .chain(surface_extensions.into_iter())
             .chain(debug::required_extension_names(validation.is_some()).into_iter())
             .collect::<Vec<_>>();
-        let instance_create_info = vk::InstanceCreateInfo::builder()
+        let mut instance_create_info = vk::InstanceCreateInfo::builder()
             .application_info(&app_info)
             .enabled_extension_names(&all_extensions)
             .enabled_layer_names(&layer_name_pointers);
+        // Installed on VkInstanceCreateInfo's pNext chain (in addition to the
+        // runtime messenger a caller creates afterwards, e.g. `DebugCtx`) so
+        // validation messages from instance creation/destruction itself are
+        // also routed through `log`.
+        let mut instance_debug_messenger_create_info = validation.map(|(message_severity, message_type)| {
+            vk::DebugUtilsMessengerCreateInfoEXT::builder()
+                .message_severity(message_severity.into())
+                .message_type(message_type.into())
+                .pfn_user_callback(Some(debug::vk_message_callback))
+                .build()
+        });
+        if let Some(instance_debug_messenger_create_info) = &mut instance_debug_messenger_create_info {
+            instance_create_info = instance_create_info.push_next(instance_debug_messenger_create_info);
+        }
         let instance = unsafe { entry.create_instance(&instance_create_info, None) }?;
+        let debug_utils = validation.is_some().then(|| DebugUtils::new(&entry, &instance));
 
         log::debug!("InstanceCtx created");
         Ok(Self {
             entry,
             instance,
             layer_names,
-            layer_name_pointers
+            layer_name_pointers,
+            debug_utils
         })
     }
 
@@ -1,61 +1,146 @@
 use std::{ffi::{CStr, c_char}, sync::Arc};
 
-use anyhow::Result;
-use ash::{Device, vk, Entry};
+use anyhow::{Result, bail};
+use ash::{Device, vk, Entry, extensions::khr};
 
 use winit::window::Window;
 
 use crate::ctx::{debug, instance::InstanceCtx};
 
 use super::{surface::SurfaceCtx, debug::{DebugCtx, MessageSeverityFlags, MessageTypeFlags}, instance::AppInfo};
+
+/// Extensions required to present to a swapchain at all; always enabled.
+fn required_device_extensions() -> Vec<&'static CStr> {
+    vec![khr::Swapchain::name()]
+}
+
+/// Extensions (and matching feature structs, checked via a
+/// `PhysicalDeviceFeatures2` pNext chain) needed for hardware ray tracing.
+/// Only required when the caller asks for `ray_tracing_required`.
+fn ray_tracing_device_extensions() -> Vec<&'static CStr> {
+    vec![
+        khr::AccelerationStructure::name(),
+        khr::RayTracingPipeline::name(),
+        khr::BufferDeviceAddress::name(),
+        // Hard-required by VK_KHR_acceleration_structure itself.
+        khr::DeferredHostOperations::name()
+    ]
+}
+
 pub struct OverallCtx {
     pub entry: Arc<Entry>,
     pub instance_ctx: Arc<InstanceCtx>,
-    pub surface_ctx: Arc<SurfaceCtx>,
+    pub surface_ctx: Option<Arc<SurfaceCtx>>,
     pub debug_ctx: Option<Arc<DebugCtx>>,
     pub device: ash::Device,
+    /// For a device built headless (`window: None`), the only queue family
+    /// index it was created with a `DeviceQueueCreateInfo` for (with a
+    /// window, the present family may get one too). `attach_surface` can only hand back a
+    /// queue from this family — `vkGetDeviceQueue` on any other family index
+    /// is invalid usage, since queue families can't be added after device
+    /// creation.
+    graphics_family_index: u32,
     pub graphics_queue: vk::Queue,
-    pub present_queue: vk::Queue
+    pub present_queue: Option<vk::Queue>
 }
 
 impl OverallCtx {
+    /// Builds the instance/device pair. With `window: Some(...)` this also
+    /// creates a `SurfaceCtx` and requires a present-capable queue, exactly
+    /// as before; with `window: None` it skips surface-extension injection
+    /// and surface creation entirely, so compute-only or offscreen
+    /// ray-tracing use doesn't need a window at all. Call `attach_surface`
+    /// afterwards to add presentation to an `OverallCtx` built headless.
     pub fn new(
-        window: Arc<Window>,
+        window: Option<Arc<Window>>,
         app_info: AppInfo,
         user_extensions: &[&CStr],
-        validation: Option<(MessageSeverityFlags, MessageTypeFlags)>
+        validation: Option<(MessageSeverityFlags, MessageTypeFlags)>,
+        ray_tracing_required: bool
     ) -> Result<Self> {
         log::debug!("OverallCtx creating");
         let entry = Arc::new(ash::Entry::linked());
-        let instance_ctx = Arc::new(InstanceCtx::new(entry.clone(), app_info, user_extensions, validation)?);
+        let instance_ctx = Arc::new(InstanceCtx::new(entry.clone(), app_info, user_extensions, validation, window.as_deref())?);
         let debug_ctx = if let Some((message_severity, message_type)) = validation {
             Some(Arc::new(debug::DebugCtx::new(instance_ctx.clone(), message_severity, message_type)?))
         } else {
             None
         };
 
-        let surface_ctx = Arc::new(SurfaceCtx::new(instance_ctx.clone(), window.clone())?);
-        let physical_device = Self::select_physical_device(&instance_ctx, &surface_ctx)?;
-        let (device, graphics_queue, present_queue) = Self::create_logical_device(&instance_ctx, &surface_ctx, physical_device, &instance_ctx.layer_name_pointers)?;
-        
+        let surface_ctx = window.as_ref()
+            .map(|window| -> Result<_> { Ok(Arc::new(SurfaceCtx::new(instance_ctx.clone(), window.clone())?)) })
+            .transpose()?;
+        let required_device_extensions = {
+            let mut extensions = required_device_extensions();
+            if ray_tracing_required {
+                extensions.extend(ray_tracing_device_extensions());
+            }
+            extensions
+        };
+        let physical_device = Self::select_physical_device(&instance_ctx, surface_ctx.as_deref(), &required_device_extensions, ray_tracing_required)?;
+        let (device, graphics_family_index, graphics_queue, present_queue) = Self::create_logical_device(&instance_ctx, surface_ctx.as_deref(), physical_device, &instance_ctx.layer_name_pointers, &required_device_extensions, ray_tracing_required)?;
+
         Ok(Self {
             entry,
             instance_ctx,
             surface_ctx,
             debug_ctx,
             device,
+            graphics_family_index,
             graphics_queue,
             present_queue
         })
     }
 
+    /// Creates a surface for `window` and resolves its present queue on an
+    /// `OverallCtx` that was built headless (`window: None`). The physical
+    /// device must actually support presenting to `window`'s surface, since
+    /// `OverallCtx::new` couldn't check that without one.
+    ///
+    /// The device was created with only `graphics_family_index` as a queue
+    /// family, so this can only succeed if the surface's present-capable
+    /// family turns out to be that same family — there's no way to add a
+    /// queue family to an already-created device. If presentation needs a
+    /// different family, the caller must build a new `OverallCtx` with the
+    /// window available up front instead.
+    pub fn attach_surface(
+        &mut self,
+        window: Arc<Window>,
+        physical_device: vk::PhysicalDevice
+    ) -> Result<()> {
+        let surface_ctx = Arc::new(SurfaceCtx::new(self.instance_ctx.clone(), window)?);
+        let (_, present_family_index) = Self::find_queue_families(&self.instance_ctx, Some(&surface_ctx), physical_device)
+            .ok_or(anyhow::anyhow!("Device cannot present to the attached surface"))?;
+        let present_family_index = present_family_index.ok_or(anyhow::anyhow!("Device cannot present to the attached surface"))?;
+        if present_family_index != self.graphics_family_index {
+            bail!(
+                "Present-capable queue family {} was not requested when the headless device was created (graphics family {}); \
+                 a new device would be needed to present on this physical device",
+                present_family_index,
+                self.graphics_family_index
+            );
+        }
+        self.present_queue = Some(unsafe { self.device.get_device_queue(present_family_index, 0) });
+        self.surface_ctx = Some(surface_ctx);
+        Ok(())
+    }
+
+    /// Enumerates physical devices, discards any without a graphics queue
+    /// (and, when `surface_ctx` is given, a present-capable one) or missing
+    /// a required extension/ray-tracing feature, then picks the
+    /// highest-scoring survivor: discrete GPUs over integrated, then larger
+    /// device-local memory, then more queue families (a proxy for richer
+    /// dedicated-transfer/async-compute support).
     fn select_physical_device(
         instance_ctx: &InstanceCtx,
-        surface_ctx: &SurfaceCtx
+        surface_ctx: Option<&SurfaceCtx>,
+        required_extensions: &[&CStr],
+        ray_tracing_required: bool
     ) -> Result<vk::PhysicalDevice> {
         let devices = unsafe { instance_ctx.instance.enumerate_physical_devices() }?;
         let device = devices.into_iter()
-            .find(|device| Self::is_device_suitable(instance_ctx, surface_ctx, *device))
+            .filter(|device| Self::is_device_suitable(instance_ctx, surface_ctx, *device, required_extensions, ray_tracing_required))
+            .max_by_key(|device| Self::score_device(instance_ctx, surface_ctx, *device))
             .ok_or(anyhow::anyhow!("No suitable physical device"))?;
         let props = unsafe { ash::Instance::get_physical_device_properties(&instance_ctx.instance, device) };
         log::debug!("Selected physical device: {:?}", unsafe {
@@ -63,20 +148,91 @@ impl OverallCtx {
         });
         Ok(device)
     }
-    
+
     fn is_device_suitable(
         instance_ctx: &InstanceCtx,
-        surface_ctx: &SurfaceCtx,
+        surface_ctx: Option<&SurfaceCtx>,
         device: vk::PhysicalDevice,
+        required_extensions: &[&CStr],
+        ray_tracing_required: bool
     ) -> bool {
         Self::find_queue_families(instance_ctx, surface_ctx, device).is_some()
+            && Self::supports_required_extensions(instance_ctx, device, required_extensions)
+            && (!ray_tracing_required || Self::supports_ray_tracing_features(instance_ctx, device))
+    }
+
+    fn supports_required_extensions(
+        instance_ctx: &InstanceCtx,
+        device: vk::PhysicalDevice,
+        required_extensions: &[&CStr]
+    ) -> bool {
+        let available_extensions = match unsafe { instance_ctx.instance.enumerate_device_extension_properties(device) } {
+            Ok(extensions) => extensions,
+            Err(_) => return false
+        };
+        required_extensions.iter().all(|required| {
+            available_extensions.iter().any(|available| unsafe {
+                CStr::from_ptr(available.extension_name.as_ptr()) == *required
+            })
+        })
+    }
+
+    fn supports_ray_tracing_features(
+        instance_ctx: &InstanceCtx,
+        device: vk::PhysicalDevice
+    ) -> bool {
+        let mut acceleration_structure_features = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::builder().build();
+        let mut ray_tracing_pipeline_features = vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::builder().build();
+        let mut buffer_device_address_features = vk::PhysicalDeviceBufferDeviceAddressFeatures::builder().build();
+        let mut features2 = vk::PhysicalDeviceFeatures2::builder()
+            .push_next(&mut acceleration_structure_features)
+            .push_next(&mut ray_tracing_pipeline_features)
+            .push_next(&mut buffer_device_address_features)
+            .build();
+        unsafe { instance_ctx.instance.get_physical_device_features2(device, &mut features2) };
+
+        acceleration_structure_features.acceleration_structure == vk::TRUE
+            && ray_tracing_pipeline_features.ray_tracing_pipeline == vk::TRUE
+            && buffer_device_address_features.buffer_device_address == vk::TRUE
+    }
+
+    /// Packs device-type preference, device-local memory size, and queue
+    /// family count into a single sortable key, highest first.
+    fn score_device(
+        instance_ctx: &InstanceCtx,
+        _surface_ctx: Option<&SurfaceCtx>,
+        device: vk::PhysicalDevice
+    ) -> (u32, u64, usize) {
+        let props = unsafe { instance_ctx.instance.get_physical_device_properties(device) };
+        let type_rank = match props.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 3,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 2,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 1,
+            _ => 0
+        };
+
+        let memory_props = unsafe { instance_ctx.instance.get_physical_device_memory_properties(device) };
+        let max_heap_size = memory_props.memory_heaps[..memory_props.memory_heap_count as usize].iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .max()
+            .unwrap_or(0);
+
+        let queue_family_count = unsafe { instance_ctx.instance.get_physical_device_queue_family_properties(device) }.len();
+
+        (type_rank, max_heap_size, queue_family_count)
     }
-    
+
+
+    /// Finds a graphics-capable queue family, plus (only when `surface_ctx`
+    /// is given) a present-capable one. Without a surface there's nothing to
+    /// present to, so a graphics queue alone is sufficient — the returned
+    /// present index is `None` rather than this failing outright.
     fn find_queue_families(
         instance_ctx: &InstanceCtx,
-        surface_ctx: &SurfaceCtx,
+        surface_ctx: Option<&SurfaceCtx>,
         device: vk::PhysicalDevice
-    ) -> Option<(u32, u32)> {
+    ) -> Option<(u32, Option<u32>)> {
         let mut graphics = None;
         let mut present = None;
         let props = unsafe { instance_ctx.instance.get_physical_device_queue_family_properties(device) };
@@ -85,29 +241,37 @@ impl OverallCtx {
             if family.queue_flags.contains(vk::QueueFlags::GRAPHICS) && graphics.is_none() {
                 graphics = Some(index);
             }
-            let present_support = unsafe { surface_ctx.surface.get_physical_device_surface_support(device, index, surface_ctx.surface_khr).unwrap_or(false) };
-            if present_support && present.is_none() {
-                present = Some(index);
+            if let Some(surface_ctx) = surface_ctx {
+                let present_support = unsafe { surface_ctx.surface.get_physical_device_surface_support(device, index, surface_ctx.surface_khr).unwrap_or(false) };
+                if present_support && present.is_none() {
+                    present = Some(index);
+                }
             }
             if let Some(graphics) = graphics {
+                if surface_ctx.is_none() {
+                    return Some((graphics, None))
+                }
                 if let Some(present) = present {
-                    return Some((graphics, present))
+                    return Some((graphics, Some(present)))
                 }
             }
         }
         None
     }
-    
+
     fn create_logical_device(
         instance_ctx: &InstanceCtx,
-        surface_ctx: &SurfaceCtx,
+        surface_ctx: Option<&SurfaceCtx>,
         physical_device: vk::PhysicalDevice,
-        layer_name_pointers: &[*const c_char]
-    ) -> Result<(Device, vk::Queue, vk::Queue)> {
+        layer_name_pointers: &[*const c_char],
+        required_extensions: &[&CStr],
+        ray_tracing_required: bool
+    ) -> Result<(Device, u32, vk::Queue, Option<vk::Queue>)> {
         let (graphics_family_index, present_family_index) = Self::find_queue_families(instance_ctx, surface_ctx, physical_device).ok_or(anyhow::anyhow!("No queue families found"))?;
         let queue_priorities = [1.0f32];
         let queue_create_infos = {
-            let mut indices = vec![graphics_family_index, present_family_index];
+            let mut indices = vec![graphics_family_index];
+            indices.extend(present_family_index);
             indices.dedup();
             indices.iter()
             .map(|index| vk::DeviceQueueCreateInfo::builder()
@@ -116,17 +280,39 @@ impl OverallCtx {
                     .build()
             ).collect::<Vec<_>>()
         };
+        let required_extensions_raw = required_extensions.iter().map(|name| name.as_ptr()).collect::<Vec<_>>();
         let device_features = vk::PhysicalDeviceFeatures::builder().build();
-        let device_create_info_builder = vk::DeviceCreateInfo::builder()
+
+        let mut acceleration_structure_features = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::builder()
+            .acceleration_structure(true)
+            .build();
+        let mut ray_tracing_pipeline_features = vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::builder()
+            .ray_tracing_pipeline(true)
+            .build();
+        let mut buffer_device_address_features = vk::PhysicalDeviceBufferDeviceAddressFeatures::builder()
+            .buffer_device_address(true)
+            .build();
+
+        let mut device_create_info_builder = vk::DeviceCreateInfo::builder()
             .queue_create_infos(&queue_create_infos)
+            .enabled_extension_names(&required_extensions_raw)
             .enabled_features(&device_features)
             .enabled_layer_names(layer_name_pointers);
+        if ray_tracing_required {
+            device_create_info_builder = device_create_info_builder
+                .push_next(&mut acceleration_structure_features)
+                .push_next(&mut ray_tracing_pipeline_features)
+                .push_next(&mut buffer_device_address_features);
+        }
         let device_create_info = device_create_info_builder.build();
         let device = unsafe { instance_ctx.instance.create_device(physical_device, &device_create_info, None)? };
         let graphics_queue = unsafe { device.get_device_queue(graphics_family_index, 0) };
-        let present_queue = unsafe { device.get_device_queue(present_family_index, 0) };
-        log::debug!("Created logical device w/ graphics queue {} & present queue {}.", graphics_family_index, present_family_index);
-        Ok((device, graphics_queue, present_queue))
+        let present_queue = present_family_index.map(|index| unsafe { device.get_device_queue(index, 0) });
+        match present_family_index {
+            Some(present_family_index) => log::debug!("Created logical device w/ graphics queue {} & present queue {}.", graphics_family_index, present_family_index),
+            None => log::debug!("Created logical device w/ graphics queue {} (no present queue).", graphics_family_index)
+        }
+        Ok((device, graphics_family_index, graphics_queue, present_queue))
     }
 }
 
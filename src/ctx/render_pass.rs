@@ -1,47 +1,153 @@
-use anyhow::Result;
-use ash::vk::{AttachmentDescription, SampleCountFlags, AttachmentLoadOp, ImageLayout, AttachmentReference, SubpassDescription, PipelineBindPoint, AttachmentStoreOp, RenderPassCreateInfo, RenderPass};
+use anyhow::{Result, anyhow};
+use ash::vk::{AttachmentDescription, SampleCountFlags, AttachmentLoadOp, ImageLayout, AttachmentReference, SubpassDescription, PipelineBindPoint, AttachmentStoreOp, RenderPassCreateInfo, RenderPass, Format, FormatFeatureFlags, SubpassDependency, self, AccessFlags, PipelineStageFlags};
 
 use super::swapchain::SwapchainCtx;
 
+/// Depth/stencil formats to try, in order of preference, when picking an
+/// attachment format to depth-test against.
+const DEPTH_FORMAT_CANDIDATES: &[Format] = &[Format::D32_SFLOAT, Format::D24_UNORM_S8_UINT, Format::D32_SFLOAT_S8_UINT];
+
 pub struct RenderPassCtx<'swp, 'dev, 'srf, 'ins, 'en> {
     pub swapchain_ctx: &'swp SwapchainCtx<'dev, 'srf, 'ins, 'en>,
-    pub render_pass: RenderPass
+    pub render_pass: RenderPass,
+    pub depth_format: Option<Format>,
+    /// Sample count selected for the colour (and depth) attachment. `TYPE_1`
+    /// means MSAA is disabled and there is no resolve attachment.
+    pub samples: SampleCountFlags
 }
 
 impl<'dev, 'srf, 'ins, 'en> SwapchainCtx<'dev, 'srf, 'ins, 'en> {
+    /// Picks the first of `DEPTH_FORMAT_CANDIDATES` whose optimal tiling
+    /// supports `DEPTH_STENCIL_ATTACHMENT` on this physical device.
+    fn select_depth_format(&self) -> Result<Format> {
+        let instance = &self.device_ctx.surface_ctx.instance_ctx.instance;
+        let physical_device = self.device_ctx.physical_info.device;
+        DEPTH_FORMAT_CANDIDATES.iter()
+            .find(|format| {
+                let props = unsafe { instance.get_physical_device_format_properties(physical_device, **format) };
+                props.optimal_tiling_features.contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+            })
+            .copied()
+            .ok_or(anyhow!("No supported depth/stencil format"))
+    }
+
+    /// Clamps `preferred_samples` against the highest sample count the
+    /// physical device's colour attachments actually support.
+    fn clamp_sample_count(&self, preferred_samples: SampleCountFlags) -> SampleCountFlags {
+        let instance = &self.device_ctx.surface_ctx.instance_ctx.instance;
+        let supported = unsafe { instance.get_physical_device_properties(self.device_ctx.physical_info.device) }
+            .limits.framebuffer_color_sample_counts;
+        [SampleCountFlags::TYPE_64, SampleCountFlags::TYPE_32, SampleCountFlags::TYPE_16, SampleCountFlags::TYPE_8, SampleCountFlags::TYPE_4, SampleCountFlags::TYPE_2]
+            .into_iter()
+            .filter(|&samples| samples.as_raw() <= preferred_samples.as_raw())
+            .find(|&samples| supported.contains(samples))
+            .unwrap_or(SampleCountFlags::TYPE_1)
+    }
+
+    /// Builds the render pass for this swapchain. `with_depth` adds a depth
+    /// attachment sized to match, and `preferred_samples` above `TYPE_1`
+    /// multisamples the colour attachment (clamped to what the device
+    /// supports) with an extra single-sample resolve attachment that targets
+    /// the swapchain image directly.
     pub fn create_render_pass_ctx(
         &self,
+        with_depth: bool,
+        preferred_samples: SampleCountFlags
     ) -> Result<RenderPassCtx> {
-        let attachment_desc = AttachmentDescription::builder()
+        let samples = self.clamp_sample_count(preferred_samples);
+        let msaa = samples != SampleCountFlags::TYPE_1;
+
+        let color_attachment_desc = AttachmentDescription::builder()
             .format(self.swapchain_image_format)
-            .samples(SampleCountFlags::TYPE_1)
+            .samples(samples)
             .load_op(AttachmentLoadOp::CLEAR)
             .store_op(AttachmentStoreOp::STORE)
             .initial_layout(ImageLayout::UNDEFINED)
-            .final_layout(ImageLayout::PRESENT_SRC_KHR)
+            .final_layout(if msaa { ImageLayout::COLOR_ATTACHMENT_OPTIMAL } else { ImageLayout::PRESENT_SRC_KHR })
             .build();
-        let attachment_descs = [attachment_desc];
-        let attachment_ref = AttachmentReference::builder()
+        let color_attachment_ref = AttachmentReference::builder()
             .attachment(0)
             .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build();
-        let attachment_refs = [attachment_ref];
-        let subpass_desc = SubpassDescription::builder()
-            .pipeline_bind_point(PipelineBindPoint::GRAPHICS)
-            .color_attachments(&attachment_refs)
+        let color_attachment_refs = [color_attachment_ref];
+
+        let mut attachment_descs = vec![color_attachment_desc];
+
+        let depth_format = if with_depth { Some(self.select_depth_format()?) } else { None };
+        let depth_attachment_ref = AttachmentReference::builder()
+            .attachment(attachment_descs.len() as u32)
+            .layout(ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .build();
+        if let Some(format) = depth_format {
+            attachment_descs.push(AttachmentDescription::builder()
+                .format(format)
+                .samples(samples)
+                .load_op(AttachmentLoadOp::CLEAR)
+                .store_op(AttachmentStoreOp::DONT_CARE)
+                .initial_layout(ImageLayout::UNDEFINED)
+                .final_layout(ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                .build());
+        }
+
+        let resolve_attachment_ref = AttachmentReference::builder()
+            .attachment(attachment_descs.len() as u32)
+            .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build();
-        let subpass_descs = [subpass_desc];
+        let resolve_attachment_refs = [resolve_attachment_ref];
+        if msaa {
+            attachment_descs.push(AttachmentDescription::builder()
+                .format(self.swapchain_image_format)
+                .samples(SampleCountFlags::TYPE_1)
+                .load_op(AttachmentLoadOp::DONT_CARE)
+                .store_op(AttachmentStoreOp::STORE)
+                .initial_layout(ImageLayout::UNDEFINED)
+                .final_layout(ImageLayout::PRESENT_SRC_KHR)
+                .build());
+        }
+
+        let mut subpass_desc_builder = SubpassDescription::builder()
+            .pipeline_bind_point(PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs);
+        if depth_format.is_some() {
+            subpass_desc_builder = subpass_desc_builder.depth_stencil_attachment(&depth_attachment_ref);
+        }
+        if msaa {
+            subpass_desc_builder = subpass_desc_builder.resolve_attachments(&resolve_attachment_refs);
+        }
+        let subpass_descs = [subpass_desc_builder.build()];
+
+        // Guards the colour attachment's CLEAR+write against the previous
+        // frame, extended with the fragment-test stages/access when a depth
+        // attachment is present, since that's also cleared and written here.
+        let mut subpass_dep_builder = SubpassDependency::builder()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(AccessFlags::empty())
+            .dst_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_access_mask(AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE);
+        if depth_format.is_some() {
+            subpass_dep_builder = subpass_dep_builder
+                .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS)
+                .dst_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS)
+                .dst_access_mask(AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+        }
+        let subpass_deps = [subpass_dep_builder.build()];
+
         let render_pass_info = RenderPassCreateInfo::builder()
             .attachments(&attachment_descs)
             .subpasses(&subpass_descs)
+            .dependencies(&subpass_deps)
             .build();
 
         let render_pass = unsafe { self.device_ctx.logical_info.device.create_render_pass(&render_pass_info, None)? };
-        
-        log::debug!("RenderPassCtx created");
+
+        log::debug!("RenderPassCtx created (depth format: {:?}, samples: {:?})", depth_format, samples);
         Ok(RenderPassCtx {
             swapchain_ctx: self,
-            render_pass
+            render_pass,
+            depth_format,
+            samples
         })
     }
 }
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use ash::{vk::{ShaderModule, ShaderModuleCreateInfo}};
 
-use super::device::DeviceCtx;
+use super::{debug::DebugCtx, device::DeviceCtx};
 
 pub struct ShaderCtx<'dev, 'srf, 'ins, 'en> {
     pub device_ctx: &'dev DeviceCtx<'srf, 'ins, 'en>,
@@ -13,12 +13,17 @@ impl<'srf, 'ins, 'en> DeviceCtx<'srf, 'ins, 'en> {
     pub fn create_shader_ctx(
         &self,
         spirv: &[u32],
-        debug_name: String
+        debug_name: String,
+        debug_ctx: Option<&DebugCtx>
     ) -> Result<ShaderCtx> {
         let create_info = ShaderModuleCreateInfo::builder()
             .code(spirv);
         let module = unsafe { self.logical_info.device.create_shader_module(&create_info, None)? };
 
+        if let Some(debug_ctx) = debug_ctx {
+            debug_ctx.set_object_name(module, &debug_name)?;
+        }
+
         log::debug!("ShaderCtx created ({})", debug_name);
         Ok(ShaderCtx {
             device_ctx: self,
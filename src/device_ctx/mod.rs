@@ -24,6 +24,7 @@ impl DeviceCtx {
         entry: &Entry,
         app_info: AppInfo,
         enabled_extension_names: &[&CStr],
+        required_device_extensions: &[&CStr],
         validation: Option<(MessageSeverityFlags, MessageTypeFlags)>
     ) -> Result<Self> {
         log::debug!("DeviceCtx creating");
@@ -43,8 +44,8 @@ impl DeviceCtx {
         } else {
             None
         };
-        let physical_device = Self::select_physical_device(&instance)?;
-        let (device, _graphics_queue) = Self::create_logical_device_with_graphics_queue(&instance, physical_device, &layer_name_pointers)?;
+        let physical_device = Self::select_physical_device(&instance, required_device_extensions)?;
+        let (device, _graphics_queue) = Self::create_logical_device_with_graphics_queue(&instance, physical_device, &layer_name_pointers, required_device_extensions)?;
         
         Ok(Self {
             instance,
@@ -71,10 +72,12 @@ impl DeviceCtx {
 
     fn select_physical_device(
         instance: &Instance,
+        required_extensions: &[&CStr],
     ) -> Result<vk::PhysicalDevice> {
         let devices = unsafe { instance.enumerate_physical_devices() }?;
         let device = devices.into_iter()
-            .find(|device| Self::is_device_suitable(instance, *device))
+            .filter(|device| Self::is_device_suitable(instance, *device, required_extensions))
+            .max_by_key(|device| Self::score_device(instance, *device))
             .ok_or(anyhow::anyhow!("No suitable physical device"))?;
         let props = unsafe { ash::Instance::get_physical_device_properties(instance.into(), device) };
         log::debug!("Selected physical device: {:?}", unsafe {
@@ -82,11 +85,48 @@ impl DeviceCtx {
         });
         Ok(device)
     }
-    
-    fn is_device_suitable(instance: &Instance, device: vk::PhysicalDevice) -> bool {
+
+    fn is_device_suitable(instance: &Instance, device: vk::PhysicalDevice, required_extensions: &[&CStr]) -> bool {
         Self::find_queue_families(instance, device).is_some()
+            && Self::supports_required_extensions(instance, device, required_extensions)
     }
-    
+
+    fn supports_required_extensions(instance: &Instance, device: vk::PhysicalDevice, required_extensions: &[&CStr]) -> bool {
+        let available_extensions = match unsafe { instance.enumerate_device_extension_properties(device) } {
+            Ok(extensions) => extensions,
+            Err(_) => return false,
+        };
+        required_extensions.iter().all(|&required| {
+            available_extensions.iter().any(|available| {
+                unsafe { CStr::from_ptr(available.extension_name.as_ptr()) == required }
+            })
+        })
+    }
+
+    /// Higher is better. Strongly prefers discrete GPUs, then breaks ties by
+    /// the size of the largest device-local memory heap, so that among
+    /// several suitable devices the one actually meant for rendering wins
+    /// rather than whichever the driver happened to enumerate first.
+    fn score_device(instance: &Instance, device: vk::PhysicalDevice) -> u64 {
+        let props = unsafe { instance.get_physical_device_properties(device) };
+        let type_score = match props.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 3,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 2,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 1,
+            _ => 0,
+        };
+
+        let memory_props = unsafe { instance.get_physical_device_memory_properties(device) };
+        let max_heap_size = memory_props.memory_heaps[..memory_props.memory_heap_count as usize].iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .max()
+            .unwrap_or(0);
+
+        (type_score << 48) | (max_heap_size >> 16)
+    }
+
+
     fn find_queue_families(instance: &Instance, device: vk::PhysicalDevice) -> Option<u32> {
         let props = unsafe { instance.get_physical_device_queue_family_properties(device) };
         props.iter().enumerate()
@@ -99,7 +139,8 @@ impl DeviceCtx {
     fn create_logical_device_with_graphics_queue(
         instance: &Instance,
         device: vk::PhysicalDevice,
-        layer_name_pointers: &[*const c_char]
+        layer_name_pointers: &[*const c_char],
+        required_device_extensions: &[&CStr]
     ) -> Result<(Device, vk::Queue)> {
         let queue_family_index = Self::find_queue_families(instance, device).ok_or(anyhow::anyhow!("No queue families found"))?;
         let queue_priorities = [1.0f32];
@@ -108,9 +149,11 @@ impl DeviceCtx {
             .queue_priorities(&queue_priorities)
             .build()];
         let device_features = vk::PhysicalDeviceFeatures::builder().build();
+        let enabled_extension_names = required_device_extensions.iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
         let device_create_info_builder = vk::DeviceCreateInfo::builder()
             .queue_create_infos(&queue_create_infos)
             .enabled_features(&device_features)
+            .enabled_extension_names(&enabled_extension_names)
             .enabled_layer_names(layer_name_pointers);
         let device_create_info = device_create_info_builder.build();
         let device = unsafe { instance.create_device(device, &device_create_info, None)? };
@@ -1,12 +1,210 @@
-use std::{fs::File, io::Read};
+use std::{collections::VecDeque, fs::File, io::Read, time::Instant};
 
 use anyhow::{Result, Context, bail};
+use ash::vk;
 use scoped_arena::Scope;
-use sierra::{Device, Fence, Surface, Queue, ImageViewCache, DynamicGraphicsPipeline, ShaderRepr, Buffer, BufferUsage, BufferInfo};
+use sierra::{Device, Fence, Surface, Queue, ImageViewCache, DynamicGraphicsPipeline, PipelineInputLayout, ShaderRepr, Buffer, BufferUsage, BufferInfo, Image, ImageInfo, ImageUsage, Format, Sampler, SamplerInfo};
 use winit::window::Window;
 
 use crate::scene::{Camera, Scene};
 
+/// Rolling CPU/GPU frame timing. GPU timing needs a pair of `TIMESTAMP`
+/// queries (one at `TOP_OF_PIPE` before the scene pass, one at
+/// `BOTTOM_OF_PIPE` after the post-process chain); it's only available when
+/// the device reports the `timestampComputeAndGraphics` limit, since some
+/// drivers can't time the graphics queue at all.
+struct FrameStats {
+    raw_device: ash::Device,
+    window: VecDeque<f32>,
+    frame_start: Instant,
+    query_pool: Option<vk::QueryPool>,
+    timestamp_period: f32,
+    last_cpu_ms: f32,
+    last_gpu_ms: Option<f32>
+}
+
+impl FrameStats {
+    const WINDOW_LEN: usize = 64;
+
+    fn new(raw_device: &ash::Device, timestamp_period: f32, supports_timestamps: bool) -> Result<Self> {
+        let query_pool = if supports_timestamps {
+            let query_pool_info = vk::QueryPoolCreateInfo::builder()
+                .query_type(vk::QueryType::TIMESTAMP)
+                .query_count(2)
+                .build();
+            Some(unsafe { raw_device.create_query_pool(&query_pool_info, None)? })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            raw_device: raw_device.clone(),
+            window: VecDeque::with_capacity(Self::WINDOW_LEN),
+            frame_start: Instant::now(),
+            query_pool,
+            timestamp_period,
+            last_cpu_ms: 0.0,
+            last_gpu_ms: None
+        })
+    }
+
+    fn begin_frame(&mut self) {
+        self.frame_start = Instant::now();
+    }
+
+    /// Writes the `TOP_OF_PIPE` timestamp; call before recording the scene
+    /// pass, once per frame.
+    fn write_start_timestamp(&self, raw_device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        if let Some(query_pool) = self.query_pool {
+            unsafe {
+                raw_device.cmd_reset_query_pool(command_buffer, query_pool, 0, 2);
+                raw_device.cmd_write_timestamp(command_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, query_pool, 0);
+            }
+        }
+    }
+
+    /// Writes the `BOTTOM_OF_PIPE` timestamp; call after the post-process
+    /// chain has been recorded, once per frame.
+    fn write_end_timestamp(&self, raw_device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        if let Some(query_pool) = self.query_pool {
+            unsafe {
+                raw_device.cmd_write_timestamp(command_buffer, vk::PipelineStageFlags::BOTTOM_OF_PIPE, query_pool, 1);
+            }
+        }
+    }
+
+    /// Reads back the GPU timestamps (if any), records the CPU time since the
+    /// last `begin_frame` into the rolling window, and keeps the GPU time for
+    /// `gpu_ms`. The query results are read with `WAIT`, so this blocks until
+    /// the most recently submitted timestamps have been written.
+    fn end_frame(&mut self, raw_device: &ash::Device) -> Result<()> {
+        let cpu_ms = self.frame_start.elapsed().as_secs_f32() * 1000.0;
+
+        let gpu_ms = match self.query_pool {
+            Some(query_pool) => {
+                let mut timestamps = [0u64; 2];
+                unsafe {
+                    raw_device.get_query_pool_results(
+                        query_pool,
+                        0,
+                        &mut timestamps,
+                        vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT
+                    )?;
+                }
+                let ticks = timestamps[1].saturating_sub(timestamps[0]);
+                Some(ticks as f32 * self.timestamp_period / 1_000_000.0)
+            },
+            None => None
+        };
+
+        if self.window.len() >= Self::WINDOW_LEN {
+            self.window.pop_front();
+        }
+        self.window.push_back(cpu_ms);
+        self.last_cpu_ms = cpu_ms;
+        self.last_gpu_ms = gpu_ms;
+
+        Ok(())
+    }
+
+    /// Instantaneous FPS, derived from the most recently recorded frame's
+    /// CPU time. `0.0` before the first frame has been recorded.
+    fn fps(&self) -> f32 {
+        if self.last_cpu_ms <= 0.0 {
+            return 0.0;
+        }
+        1000.0 / self.last_cpu_ms
+    }
+
+    /// FPS averaged over the rolling window, smoothed against single-frame
+    /// spikes (e.g. a stutter from a one-off allocation). `0.0` before any
+    /// frame has been recorded.
+    fn average_fps(&self) -> f32 {
+        if self.window.is_empty() {
+            return 0.0;
+        }
+        let average_ms = self.window.iter().sum::<f32>() / self.window.len() as f32;
+        if average_ms <= 0.0 {
+            return 0.0;
+        }
+        1000.0 / average_ms
+    }
+
+    /// Milliseconds the GPU spent on the last frame's scene and
+    /// post-process passes, or `None` if timestamp queries aren't
+    /// supported on this device.
+    fn gpu_ms(&self) -> Option<f32> {
+        self.last_gpu_ms
+    }
+}
+
+impl Drop for FrameStats {
+    fn drop(&mut self) {
+        if let Some(query_pool) = self.query_pool {
+            unsafe {
+                self.raw_device.destroy_query_pool(query_pool, None);
+            }
+        }
+    }
+}
+
+/// One fullscreen-fragment-shader pass applied to the previous pass's
+/// output before it reaches the swapchain image. Entry point names match
+/// `shaders/src/lib.rs`.
+const POST_PROCESS_PASS_SHADERS: &[&str] = &["main_fs_post_tonemap", "main_fs_post_vignette"];
+
+#[derive(sierra::PipelineInput)]
+struct PostProcessInput {
+    #[sierra(combined_image_sampler, fragment)]
+    input_color: sierra::CombinedImageSampler
+}
+
+struct PostProcessChain {
+    pipeline_layout: PipelineInputLayout,
+    passes: Vec<DynamicGraphicsPipeline>,
+    sampler: Sampler,
+    /// Ping-pong intermediate targets the scene pass and all but the last
+    /// post-process pass render into; the last pass renders straight to the
+    /// swapchain image instead of a third target.
+    ping_pong_images: [Image; 2]
+}
+
+impl PostProcessChain {
+    fn new(
+        device: &Device,
+        shader_module: &sierra::ShaderModule,
+        extent: [u32; 2]
+    ) -> Result<Self> {
+        let pipeline_layout = PostProcessInput::layout(device)?;
+        let passes = POST_PROCESS_PASS_SHADERS.iter()
+            .map(|entry_point| DynamicGraphicsPipeline::new(sierra::graphics_pipeline_desc!(
+                layout: pipeline_layout.raw().clone(),
+                vertex_shader: sierra::VertexShader::new(shader_module.clone(), "main_vs_fullscreen"),
+                fragment_shader: Some(sierra::FragmentShader::new(shader_module.clone(), *entry_point)),
+            )))
+            .collect();
+
+        let sampler = device.create_sampler(SamplerInfo::linear())?;
+        let ping_pong_images = [
+            Self::create_target(device, extent)?,
+            Self::create_target(device, extent)?,
+        ];
+
+        Ok(Self { pipeline_layout, passes, sampler, ping_pong_images })
+    }
+
+    fn create_target(device: &Device, extent: [u32; 2]) -> Result<Image> {
+        Ok(device.create_image(ImageInfo {
+            extent: extent.into(),
+            format: Format::RGBA32Sfloat,
+            usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+            levels: 1,
+            layers: 1,
+            samples: sierra::Samples::Samples1
+        })?)
+    }
+}
+
 #[derive(sierra::PipelineInput)]
 struct PipelineInput {
     #[sierra(push(std430), vertex)]
@@ -34,7 +232,9 @@ impl CameraUniforms {
 struct SceneData {
     pub vertex_buffer: Buffer,
     pub vertex_buffer_offset: u64,
-    pub vertex_count: u32
+    pub index_buffer: Buffer,
+    pub index_buffer_offset: u64,
+    pub index_count: u32
 }
 
 pub struct Renderer<'a> {
@@ -48,10 +248,13 @@ pub struct Renderer<'a> {
     view_cache: ImageViewCache,
 
     scene_data: SceneData,
+    post_process: PostProcessChain,
 
     fences: Vec<Option<Fence>>,
     fence_index: usize,
-    non_optimal_count: u32
+    non_optimal_count: u32,
+
+    frame_stats: FrameStats
 }
 
 impl Renderer<'_> {
@@ -65,6 +268,8 @@ impl Renderer<'_> {
         let scope = Scope::new();
         let graphics = sierra::Graphics::get_or_init()?;
         let physical = graphics.devices()?.into_iter().max_by_key(|d| d.info().kind).context("No physical device found")?;
+        let supports_timestamps = physical.info().limits.timestamp_compute_and_graphics;
+        let timestamp_period = physical.info().limits.timestamp_period;
 
         let features = [
             sierra::Feature::DynamicRendering,
@@ -79,6 +284,7 @@ impl Renderer<'_> {
             }
         }
         let (device, queue) = physical.create_device(&features, sierra::SingleQueueQuery::GRAPHICS)?;
+        let frame_stats = FrameStats::new(device.raw(), timestamp_period, supports_timestamps)?;
 
         let shader_module = {
             let shader_bytes = File::open("in/spirv/shaders.spv")?.bytes().try_collect::<Vec<_>>()?;
@@ -97,9 +303,10 @@ impl Renderer<'_> {
 
         let view_cache = sierra::ImageViewCache::new();
 
+        let post_process = PostProcessChain::new(&device, &shader_module, window.inner_size().into())?;
+
         let scene_data = {
             let vertex_data = bytemuck::cast_slice(&scene.vertices) as &[u8];
-
             let vertex_buffer = device.create_buffer_static(
                 BufferInfo {
                     align: 255,
@@ -109,10 +316,22 @@ impl Renderer<'_> {
                 vertex_data
             )?;
 
+            let index_data = bytemuck::cast_slice(&scene.indices) as &[u8];
+            let index_buffer = device.create_buffer_static(
+                BufferInfo {
+                    align: 255,
+                    size: index_data.len() as u64,
+                    usage: BufferUsage::INDEX
+                },
+                index_data
+            )?;
+
             SceneData {
                 vertex_buffer,
-                vertex_buffer_offset: 0, 
-                vertex_count: scene.vertices.len() as u32
+                vertex_buffer_offset: 0,
+                index_buffer,
+                index_buffer_offset: 0,
+                index_count: scene.indices.len() as u32
             }
         };
         
@@ -127,13 +346,34 @@ impl Renderer<'_> {
             view_cache,
 
             scene_data,
+            post_process,
 
             fences: (0..Self::FRAMES_IN_FLIGHT).into_iter().map(|_| None).collect(),
             fence_index: 0,
-            non_optimal_count: 0
+            non_optimal_count: 0,
+
+            frame_stats
         })
     }
 
+    /// Instantaneous frames-per-second, derived from the most recently
+    /// completed frame's CPU time.
+    pub fn fps(&self) -> f32 {
+        self.frame_stats.fps()
+    }
+
+    /// Frames-per-second averaged over a rolling window, smoothed against
+    /// single-frame stutters.
+    pub fn average_fps(&self) -> f32 {
+        self.frame_stats.average_fps()
+    }
+
+    /// Milliseconds the GPU spent on the most recently completed frame, or
+    /// `None` if timestamp queries aren't supported on this device.
+    pub fn gpu_ms(&self) -> Option<f32> {
+        self.frame_stats.gpu_ms()
+    }
+
     pub fn wait_idle(
         &self
     ) -> Result<()> {
@@ -148,15 +388,27 @@ impl Renderer<'_> {
         if let Some(fence) = &mut self.fences[self.fence_index] {
             self.device.wait_fences(&mut [fence], true)?;
             self.device.reset_fences(&mut [fence])?;
-        }  
+            // Records the previous frame's timings. The query pool is shared
+            // by every frame slot, so it holds the timestamps of the most
+            // recently submitted frame rather than of the frame this fence
+            // belongs to; `end_frame` waits for those timestamps to be
+            // written.
+            self.frame_stats.end_frame(self.device.raw())?;
+        }
+        self.frame_stats.begin_frame();
         let mut image = self.surface.acquire_image()?;
         let mut encoder = self.queue.create_encoder(&self.scope)?;
+        self.frame_stats.write_start_timestamp(self.device.raw(), encoder.raw());
 
+        // Scene geometry renders into the first ping-pong target rather than
+        // the swapchain image directly, so the post-process chain below has
+        // something to resample before the result reaches the screen.
+        let scene_target = self.post_process.ping_pong_images[0].clone();
         encoder.image_barriers(
             sierra::PipelineStages::COLOR_ATTACHMENT_OUTPUT,
             sierra::PipelineStages::COLOR_ATTACHMENT_OUTPUT,
             &[sierra::ImageMemoryBarrier::initialize_whole(
-                image.image(),
+                &scene_target,
                 sierra::Access::COLOR_ATTACHMENT_WRITE,
                 sierra::Layout::ColorAttachmentOptimal,
             )],
@@ -166,19 +418,20 @@ impl Renderer<'_> {
             let mut render_pass_encoder = encoder.begin_rendering(
                 sierra::RenderingInfo::new().color(
                     &sierra::RenderingColorInfo::new(
-                        self.view_cache.make_image(image.image(), &self.device)?.clone(),
+                        self.view_cache.make_image(&scene_target, &self.device)?.clone(),
                     )
                     .clear(sierra::ClearColor(0.3, 0.1, 0.8, 1.0)),
                 ),
             );
             render_pass_encoder.bind_dynamic_graphics_pipeline(&mut self.graphics_pipeline, &self.device)?;
-            // render_pass_encoder.bind_vertex_buffers(0, &mut [(&self.scene_data.vertex_buffer, self.scene_data.vertex_buffer_offset)]);
-            // render_pass_encoder.draw(0..self.scene_data.vertex_count, 0..1);
-            dbg!(CameraUniforms::from_camera(camera).proj);
+            render_pass_encoder.bind_vertex_buffers(0, &mut [(&self.scene_data.vertex_buffer, self.scene_data.vertex_buffer_offset)]);
+            render_pass_encoder.bind_index_buffer(&self.scene_data.index_buffer, self.scene_data.index_buffer_offset, sierra::IndexType::U32);
             render_pass_encoder.push_constants(&self.pipeline_layout, &CameraUniforms::from_camera(camera));
-            render_pass_encoder.draw(0..3, 0..1);
+            render_pass_encoder.draw_indexed(0..self.scene_data.index_count, 0, 0..1);
         }
 
+        self.run_post_process_chain(&mut encoder, scene_target, image.image().clone())?;
+
         encoder.image_barriers(
             sierra::PipelineStages::COLOR_ATTACHMENT_OUTPUT,
             sierra::PipelineStages::TOP_OF_PIPE,
@@ -188,6 +441,7 @@ impl Renderer<'_> {
                 sierra::Layout::ColorAttachmentOptimal..sierra::Layout::Present,
             )],
         );
+        self.frame_stats.write_end_timestamp(self.device.raw(), encoder.raw());
 
         let [wait, signal] = image.wait_signal();
         let fence = match &mut self.fences[self.fence_index] {
@@ -218,7 +472,66 @@ impl Renderer<'_> {
             self.non_optimal_count = 0;
         }
         self.scope.reset();
-        
+
+        Ok(())
+    }
+
+    /// Runs each post-process pass as a fullscreen triangle over the
+    /// previous pass's output, ping-ponging between the two intermediate
+    /// targets, with the last pass drawing straight into `present_target`.
+    fn run_post_process_chain(
+        &mut self,
+        encoder: &mut sierra::Encoder,
+        mut input: sierra::Image,
+        present_target: sierra::Image
+    ) -> Result<()> {
+        let pass_count = self.post_process.passes.len();
+        for (i, pipeline) in self.post_process.passes.iter_mut().enumerate() {
+            let is_last_pass = i == pass_count - 1;
+            let output = if is_last_pass {
+                present_target.clone()
+            } else {
+                self.post_process.ping_pong_images[(i + 1) % 2].clone()
+            };
+
+            encoder.image_barriers(
+                sierra::PipelineStages::COLOR_ATTACHMENT_OUTPUT,
+                sierra::PipelineStages::FRAGMENT_SHADER,
+                &[sierra::ImageMemoryBarrier::transition_whole(
+                    &input,
+                    sierra::Access::COLOR_ATTACHMENT_WRITE..sierra::Access::SHADER_READ,
+                    sierra::Layout::ColorAttachmentOptimal..sierra::Layout::ShaderReadOnlyOptimal,
+                )],
+            );
+            encoder.image_barriers(
+                sierra::PipelineStages::TOP_OF_PIPE,
+                sierra::PipelineStages::COLOR_ATTACHMENT_OUTPUT,
+                &[sierra::ImageMemoryBarrier::initialize_whole(
+                    &output,
+                    sierra::Access::COLOR_ATTACHMENT_WRITE,
+                    sierra::Layout::ColorAttachmentOptimal,
+                )],
+            );
+
+            {
+                let mut pass_encoder = encoder.begin_rendering(
+                    sierra::RenderingInfo::new().color(
+                        &sierra::RenderingColorInfo::new(self.view_cache.make_image(&output, &self.device)?.clone()),
+                    ),
+                );
+                pass_encoder.bind_dynamic_graphics_pipeline(pipeline, &self.device)?;
+                pass_encoder.bind_inputs(&self.post_process.pipeline_layout, &PostProcessInput {
+                    input_color: sierra::CombinedImageSampler::new(
+                        self.view_cache.make_image(&input, &self.device)?.clone(),
+                        self.post_process.sampler.clone(),
+                    ),
+                })?;
+                pass_encoder.draw(0..3, 0..1);
+            }
+
+            input = output;
+        }
+
         Ok(())
     }
 }
\ No newline at end of file
@@ -35,23 +35,29 @@ impl Camera {
 }
 
 pub struct Scene {
-    pub vertices: Vec<GeoVertex>
+    pub vertices: Vec<GeoVertex>,
+    pub indices: Vec<u32>
 }
 
 impl Scene {
     pub fn from_obj_righthanded(
         obj: Obj<obj::Vertex, u16>
     ) -> Result<Self> {
-        let vertices = obj.indices.iter()
+        let vertices: Vec<GeoVertex> = obj.indices.iter()
             .map(|index| *obj.vertices.get(*index as usize).unwrap())
             .map(|vertex| GeoVertex {
                 position: [vertex.position[0], vertex.position[1], -vertex.position[2]],
                 normal: [vertex.normal[0], vertex.normal[1], -vertex.normal[2]]
             })
             .collect();
+        // `vertices` is already one entry per `obj.indices` member, so the
+        // index buffer is the identity mapping for now; real deduplication
+        // is a separate concern from staging the buffers to the device.
+        let indices = (0..vertices.len() as u32).collect();
 
         Ok(Self {
-            vertices
+            vertices,
+            indices
         })
     }
 }
\ No newline at end of file
@@ -7,7 +7,7 @@ use super::app_info::AppInfo;
 
 mod validation;
 
-pub use validation::{MessageSeverityFlags, MessageTypeFlags};
+pub use validation::{MessageSeverityFlags, MessageTypeFlags, UserCallback};
 
 pub struct Instance<'entry> {
     instance: ash::Instance,
@@ -23,7 +23,8 @@ impl<'entry> Instance<'entry> {
         entry: &'entry ash::Entry,
         app_info: AppInfo,
         enabled_extension_names: &[&CStr],
-        enable_validation: Option<(MessageSeverityFlags, MessageTypeFlags)>
+        enable_validation: Option<(MessageSeverityFlags, MessageTypeFlags)>,
+        validation_callback: Option<UserCallback>
     ) -> Result<Self> {
         log::debug!("Instance creating");
         let validation_layers_c = VALIDATION_LAYERS.iter().cloned()
@@ -47,11 +48,11 @@ impl<'entry> Instance<'entry> {
         let instance = unsafe { entry.create_instance(&instance_create_info, None) }?;
 
         let debug_utils_messenger = if let Some((message_severity, message_type)) = enable_validation {
-            Some(validation::DebugUtilsMessenger::new(entry, &instance, message_severity, message_type)?)
+            Some(validation::DebugUtilsMessenger::new(entry, &instance, message_severity, message_type, validation_callback)?)
         } else {
             None
         };
-        
+
         Ok(Self {
             instance,
             debug_utils_messenger,
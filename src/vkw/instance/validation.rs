@@ -1,7 +1,7 @@
-use std::ffi::{c_void, CStr};
+use std::{ffi::{c_void, CStr, CString}, ptr};
 
 use anyhow::{Result, bail};
-use ash::{extensions::ext::DebugUtils, vk::{DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, Bool32, DebugUtilsMessengerCallbackDataEXT, self, DebugUtilsMessengerEXT}, Entry, Instance};
+use ash::{extensions::ext::DebugUtils, vk::{DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, Bool32, DebugUtilsMessengerCallbackDataEXT, self, DebugUtilsMessengerEXT}, Entry, Instance, Device};
 
 #[derive(Debug, strum_macros::Display)]
 pub enum MessageSeverity {
@@ -166,29 +166,49 @@ impl From<MessageTypeFlags> for DebugUtilsMessageTypeFlagsEXT {
         severity
     }
 }
+/// A caller-supplied sink for validation messages, as an alternative (or
+/// addition) to the `log` crate routing `vk_message_callback` does by
+/// default — e.g. to forward messages into an application's own telemetry.
+pub type UserCallback = Box<dyn Fn(MessageSeverity, MessageType, &str) + 'static>;
+
 pub struct DebugUtilsMessenger {
     debug_utils: DebugUtils,
-    messenger: DebugUtilsMessengerEXT
+    messenger: DebugUtilsMessengerEXT,
+    /// Boxed again so `p_user_data` can hold a stable, thin pointer to this
+    /// fat trait-object pointer rather than the fat pointer itself.
+    user_callback: Option<Box<UserCallback>>
 }
 
 impl DebugUtilsMessenger {
     unsafe extern "system" fn vk_message_callback(
         message_severity: DebugUtilsMessageSeverityFlagsEXT,
-        _message_types: DebugUtilsMessageTypeFlagsEXT,
+        message_types: DebugUtilsMessageTypeFlagsEXT,
         callback_data: *const DebugUtilsMessengerCallbackDataEXT,
-        _user_data: *mut c_void,
+        user_data: *mut c_void,
     ) -> Bool32 {
-        let severity_str = if let Ok(message_severity) = MessageSeverity::try_from(message_severity) {
-            message_severity.to_string()
-        } else {
-            "(vkw: unknown)".to_string()
-        };
+        let severity = MessageSeverity::try_from(message_severity).ok();
+        let message_type = MessageType::try_from(message_types).ok();
         let message = if let Some(callback_data) = callback_data.as_ref() {
             CStr::from_ptr(callback_data.p_message).to_str().unwrap_or("(vkw: could not read p_message)")
         } else {
             "(vkw: could not read callback_data)"
         };
-        log::debug!("[VK/{}] {}", severity_str, message); 
+
+        let severity_str = severity.as_ref().map(MessageSeverity::to_string).unwrap_or_else(|| "(vkw: unknown)".to_string());
+        let type_str = message_type.as_ref().map(MessageType::to_string).unwrap_or_else(|| "(vkw: unknown)".to_string());
+        let formatted = format!("[VK/{}/{}] {}", severity_str, type_str, message);
+        match message_severity {
+            DebugUtilsMessageSeverityFlagsEXT::ERROR => log::error!("{}", formatted),
+            DebugUtilsMessageSeverityFlagsEXT::WARNING => log::warn!("{}", formatted),
+            DebugUtilsMessageSeverityFlagsEXT::VERBOSE => log::trace!("{}", formatted),
+            _ => log::debug!("{}", formatted)
+        }
+
+        if let (false, Some(severity), Some(message_type)) = (user_data.is_null(), severity, message_type) {
+            let user_callback = &*(user_data as *const UserCallback);
+            user_callback(severity, message_type, message);
+        }
+
         vk::FALSE
     }
 
@@ -196,19 +216,27 @@ impl DebugUtilsMessenger {
         entry: &Entry,
         instance: &Instance,
         message_severity: MessageSeverityFlags,
-        message_type: MessageTypeFlags
+        message_type: MessageTypeFlags,
+        user_callback: Option<UserCallback>
     ) -> Result<Self> {
         log::debug!("DebugUtilsMessenger creating");
         let debug_utils = DebugUtils::new(entry, instance);
+        let user_callback = user_callback.map(Box::new);
+        let user_data = match &user_callback {
+            Some(boxed) => boxed.as_ref() as *const UserCallback as *mut c_void,
+            None => ptr::null_mut()
+        };
         let create_info = DebugUtilsMessengerCreateInfoEXT::builder()
             .message_severity(message_severity.into())
             .message_type(message_type.into())
-            .pfn_user_callback(Some(Self::vk_message_callback));
+            .pfn_user_callback(Some(Self::vk_message_callback))
+            .user_data(user_data);
         let messenger = unsafe { debug_utils.create_debug_utils_messenger(&create_info, None) }?;
-        
+
         Ok(Self {
             debug_utils,
-            messenger
+            messenger,
+            user_callback
         })
     }
 
@@ -216,4 +244,62 @@ impl DebugUtilsMessenger {
         self.debug_utils.destroy_debug_utils_messenger(self.messenger, None);
         log::debug!("DebugUtilsMessenger dropped");
     }
+
+    /// Gives a Vulkan object a human-readable name, so RenderDoc captures
+    /// and validation messages reference it instead of an opaque handle.
+    pub fn set_object_name<T: vk::Handle>(
+        &self,
+        device: &Device,
+        handle: T,
+        name: &str
+    ) -> Result<()> {
+        let name_c = CString::new(name)?;
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(T::TYPE)
+            .object_handle(handle.as_raw())
+            .object_name(&name_c);
+        unsafe { self.debug_utils.set_debug_utils_object_name(device.handle(), &name_info)? };
+        Ok(())
+    }
+
+    /// Opens a named, coloured label region in `command_buffer`; must be
+    /// matched with `cmd_end_label`. Regions nest, and show up around the
+    /// commands they bracket in RenderDoc/validation output.
+    pub fn cmd_begin_label(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        name: &str,
+        color: [f32; 4]
+    ) -> Result<()> {
+        let name_c = CString::new(name)?;
+        let label = vk::DebugUtilsLabelEXT::builder()
+            .label_name(&name_c)
+            .color(color);
+        unsafe { self.debug_utils.cmd_begin_debug_utils_label(command_buffer, &label) };
+        Ok(())
+    }
+
+    /// Closes the most recently opened `cmd_begin_label` region.
+    pub fn cmd_end_label(
+        &self,
+        command_buffer: vk::CommandBuffer
+    ) {
+        unsafe { self.debug_utils.cmd_end_debug_utils_label(command_buffer) };
+    }
+
+    /// Inserts a single named, coloured marker at this point in
+    /// `command_buffer`, without opening a region.
+    pub fn cmd_insert_label(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        name: &str,
+        color: [f32; 4]
+    ) -> Result<()> {
+        let name_c = CString::new(name)?;
+        let label = vk::DebugUtilsLabelEXT::builder()
+            .label_name(&name_c)
+            .color(color);
+        unsafe { self.debug_utils.cmd_insert_debug_utils_label(command_buffer, &label) };
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,134 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::Result;
+use ash::vk;
+use gpu_allocator::{
+    vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator, AllocatorCreateDesc},
+    MemoryLocation
+};
+
+use super::device::DeviceCtx;
+
+/// Suballocates device memory via `gpu-allocator` instead of every caller
+/// hand-rolling `vkAllocateMemory` and heap-index selection. `allocate_buffer`
+/// and `allocate_image` hand back RAII wrappers that free their suballocation
+/// (and destroy the buffer/image) on `Drop`.
+pub struct AllocatorCtx {
+    pub device_ctx: Rc<DeviceCtx>,
+    allocator: RefCell<Allocator>
+}
+
+impl AllocatorCtx {
+    pub fn new(
+        device_ctx: Rc<DeviceCtx>
+    ) -> Result<Rc<AllocatorCtx>> {
+        let allocator = Allocator::new(&AllocatorCreateDesc {
+            instance: device_ctx.surface_ctx.instance_ctx.instance.clone(),
+            device: device_ctx.logical_info.device.clone(),
+            physical_device: device_ctx.physical_info.device,
+            debug_settings: Default::default(),
+            buffer_device_address: false,
+            allocation_sizes: Default::default()
+        })?;
+
+        log::debug!("AllocatorCtx created");
+        Ok(Rc::new(AllocatorCtx {
+            device_ctx,
+            allocator: RefCell::new(allocator)
+        }))
+    }
+
+    pub fn allocate_buffer(
+        self: &Rc<Self>,
+        size: u64,
+        usage: vk::BufferUsageFlags,
+        location: MemoryLocation
+    ) -> Result<AllocatedBuffer> {
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let buffer = unsafe { self.device_ctx.logical_info.device.create_buffer(&buffer_info, None)? };
+        let requirements = unsafe { self.device_ctx.logical_info.device.get_buffer_memory_requirements(buffer) };
+
+        let allocation = self.allocator.borrow_mut().allocate(&AllocationCreateDesc {
+            name: "AllocatorCtx buffer",
+            requirements,
+            location,
+            linear: true,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged
+        })?;
+        unsafe { self.device_ctx.logical_info.device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset())? };
+
+        log::debug!("AllocatorCtx allocated buffer ({} bytes, {:?})", size, location);
+        Ok(AllocatedBuffer {
+            allocator_ctx: self.clone(),
+            buffer,
+            allocation: Some(allocation)
+        })
+    }
+
+    pub fn allocate_image(
+        self: &Rc<Self>,
+        image_info: &vk::ImageCreateInfo,
+        location: MemoryLocation
+    ) -> Result<AllocatedImage> {
+        let image = unsafe { self.device_ctx.logical_info.device.create_image(image_info, None)? };
+        let requirements = unsafe { self.device_ctx.logical_info.device.get_image_memory_requirements(image) };
+
+        let allocation = self.allocator.borrow_mut().allocate(&AllocationCreateDesc {
+            name: "AllocatorCtx image",
+            requirements,
+            location,
+            linear: false,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged
+        })?;
+        unsafe { self.device_ctx.logical_info.device.bind_image_memory(image, allocation.memory(), allocation.offset())? };
+
+        log::debug!("AllocatorCtx allocated image ({:?})", location);
+        Ok(AllocatedImage {
+            allocator_ctx: self.clone(),
+            image,
+            allocation: Some(allocation)
+        })
+    }
+}
+
+// SUPPORTING TYPES
+
+pub struct AllocatedBuffer {
+    pub allocator_ctx: Rc<AllocatorCtx>,
+    pub buffer: vk::Buffer,
+    allocation: Option<Allocation>
+}
+
+impl Drop for AllocatedBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.allocator_ctx.device_ctx.logical_info.device.destroy_buffer(self.buffer, None);
+        }
+        if let Some(allocation) = self.allocation.take() {
+            let _ = self.allocator_ctx.allocator.borrow_mut().free(allocation);
+        }
+        log::debug!("AllocatedBuffer dropped");
+    }
+}
+
+pub struct AllocatedImage {
+    pub allocator_ctx: Rc<AllocatorCtx>,
+    pub image: vk::Image,
+    allocation: Option<Allocation>
+}
+
+impl Drop for AllocatedImage {
+    fn drop(&mut self) {
+        unsafe {
+            self.allocator_ctx.device_ctx.logical_info.device.destroy_image(self.image, None);
+        }
+        if let Some(allocation) = self.allocation.take() {
+            let _ = self.allocator_ctx.allocator.borrow_mut().free(allocation);
+        }
+        log::debug!("AllocatedImage dropped");
+    }
+}
@@ -0,0 +1,126 @@
+use std::{ffi::CString, rc::Rc};
+
+use ash::vk::{DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, ShaderStageFlags, DescriptorSetLayout, PipelineLayoutCreateInfo, PipelineLayout, PipelineShaderStageCreateInfo, ComputePipelineCreateInfo, PipelineCache, Pipeline, CommandBuffer, PipelineBindPoint, PipelineStageFlags, AccessFlags, MemoryBarrier, DependencyFlags};
+use anyhow::{Result, bail};
+
+use super::{device::DeviceCtx, shader::ShaderCtx};
+
+/// Particle/pre-pass simulation buffers are bound as two storage buffers:
+/// the current state to read, and the next state to write. A real
+/// implementation would ping-pong between two such sets across frames.
+const PARTICLE_BUFFER_BINDING: u32 = 0;
+const OUTPUT_BUFFER_BINDING: u32 = 1;
+
+pub struct ComputePipelineCtx {
+    pub device_ctx: Rc<DeviceCtx>,
+    pub shader_ctx: Rc<ShaderCtx>,
+    pub descriptor_set_layout: DescriptorSetLayout,
+    pub pipeline_layout: PipelineLayout,
+    pub pipeline: Pipeline
+}
+
+impl ComputePipelineCtx {
+    pub fn new(
+        device_ctx: Rc<DeviceCtx>,
+        shader_ctx: Rc<ShaderCtx>
+    ) -> Result<Rc<ComputePipelineCtx>> {
+        let entry_point_name = CString::new("main_cs")?;
+
+        let bindings = [
+            DescriptorSetLayoutBinding::builder()
+                .binding(PARTICLE_BUFFER_BINDING)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(ShaderStageFlags::COMPUTE)
+                .build(),
+            DescriptorSetLayoutBinding::builder()
+                .binding(OUTPUT_BUFFER_BINDING)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(ShaderStageFlags::COMPUTE)
+                .build(),
+        ];
+        let descriptor_set_layout_info = DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&bindings)
+            .build();
+        let descriptor_set_layout = unsafe { device_ctx.logical_info.device.create_descriptor_set_layout(&descriptor_set_layout_info, None)? };
+        let descriptor_set_layouts = [descriptor_set_layout];
+
+        let pipeline_layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(&descriptor_set_layouts)
+            .build();
+        let pipeline_layout = unsafe { device_ctx.logical_info.device.create_pipeline_layout(&pipeline_layout_info, None)? };
+
+        let shader_stage_info = PipelineShaderStageCreateInfo::builder()
+            .stage(ShaderStageFlags::COMPUTE)
+            .module(shader_ctx.module)
+            .name(&entry_point_name)
+            .build();
+
+        let pipeline_info = ComputePipelineCreateInfo::builder()
+            .stage(shader_stage_info)
+            .layout(pipeline_layout)
+            .build();
+        let pipeline_infos = [pipeline_info];
+        let maybe_pipelines = unsafe { device_ctx.logical_info.device.create_compute_pipelines(PipelineCache::null(), &pipeline_infos, None) };
+        let pipelines = match maybe_pipelines {
+            Ok(pipelines) => pipelines,
+            Err((_, result)) => bail!(result)
+        };
+        let pipeline = pipelines[0];
+
+        log::debug!("ComputePipelineCtx created");
+        Ok(Rc::new(ComputePipelineCtx {
+            device_ctx,
+            shader_ctx,
+            descriptor_set_layout,
+            pipeline_layout,
+            pipeline
+        }))
+    }
+
+    /// Records a dispatch of `group_count` workgroups into `command_buffer`,
+    /// which the caller is expected to have already bound a descriptor set
+    /// built against `descriptor_set_layout` to. Also records the memory
+    /// barrier needed before the output buffer is safe to read as vertex
+    /// input, so callers don't have to remember it themselves.
+    pub fn dispatch(
+        &self,
+        command_buffer: CommandBuffer,
+        group_count: [u32; 3]
+    ) {
+        unsafe {
+            let device = &self.device_ctx.logical_info.device;
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, self.pipeline);
+            device.cmd_dispatch(command_buffer, group_count[0], group_count[1], group_count[2]);
+
+            // The particle/pre-pass buffer this dispatch writes is consumed
+            // by the vertex stage; without this barrier the vertex stage can
+            // read it before the compute shader's writes land.
+            let memory_barrier = MemoryBarrier::builder()
+                .src_access_mask(AccessFlags::SHADER_WRITE)
+                .dst_access_mask(AccessFlags::SHADER_READ | AccessFlags::VERTEX_ATTRIBUTE_READ)
+                .build();
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::VERTEX_INPUT | PipelineStageFlags::VERTEX_SHADER,
+                DependencyFlags::empty(),
+                &[memory_barrier],
+                &[],
+                &[],
+            );
+        }
+    }
+}
+
+impl Drop for ComputePipelineCtx {
+    fn drop(&mut self) {
+        unsafe {
+            self.device_ctx.logical_info.device.destroy_pipeline(self.pipeline, None);
+            self.device_ctx.logical_info.device.destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device_ctx.logical_info.device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+        log::debug!("ComputePipelineCtx dropped");
+    }
+}
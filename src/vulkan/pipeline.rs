@@ -1,13 +1,27 @@
 use std::{ffi::CString, rc::Rc};
 
-use ash::vk::{PipelineVertexInputStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PrimitiveTopology, Viewport, Rect2D, Offset2D, PipelineViewportStateCreateInfo, PipelineRasterizationStateCreateInfo, PolygonMode, CullModeFlags, FrontFace, PipelineMultisampleStateCreateInfo, SampleCountFlags, PipelineColorBlendAttachmentState, ColorComponentFlags, BlendFactor, BlendOp, LogicOp, PipelineColorBlendStateCreateInfo, PipelineLayoutCreateInfo, PipelineLayout, PipelineShaderStageCreateInfo, ShaderStageFlags, GraphicsPipelineCreateInfo, PipelineCache, Pipeline};
+use ash::vk::{PipelineVertexInputStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PrimitiveTopology, Viewport, Rect2D, Offset2D, PipelineViewportStateCreateInfo, PipelineRasterizationStateCreateInfo, PolygonMode, CullModeFlags, FrontFace, PipelineMultisampleStateCreateInfo, SampleCountFlags, PipelineColorBlendAttachmentState, ColorComponentFlags, BlendFactor, BlendOp, LogicOp, PipelineColorBlendStateCreateInfo, PipelineLayoutCreateInfo, PipelineLayout, PipelineShaderStageCreateInfo, ShaderStageFlags, GraphicsPipelineCreateInfo, PipelineCache, Pipeline, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, DescriptorSetLayout};
 use anyhow::{Result, bail};
 
 use super::{render_pass::RenderPassCtx, shader::ShaderCtx};
 
+/// Binding within the vertex-pulling descriptor set (set 0): the storage
+/// buffer the vertex shader indexes into with `gl_VertexIndex` instead of
+/// reading fixed vertex input.
+const VERTEX_STORAGE_BUFFER_BINDING: u32 = 0;
+
+/// Bindings within the camera descriptor set (set 1): the combined
+/// view-projection matrix used to place vertices, and the view matrix alone
+/// for shading math (e.g. transforming normals into view space) that
+/// shouldn't also carry the projection.
+const CAMERA_VIEW_PROJ_BINDING: u32 = 0;
+const CAMERA_VIEW_BINDING: u32 = 1;
+
 pub struct PipelineCtx {
     pub render_pass_ctx: Rc<RenderPassCtx>,
     pub shader_ctx: Rc<ShaderCtx>,
+    pub vertex_descriptor_set_layout: DescriptorSetLayout,
+    pub camera_descriptor_set_layout: DescriptorSetLayout,
     pub pipeline_layout: PipelineLayout,
     pub pipeline: Pipeline
 }
@@ -31,7 +45,11 @@ impl PipelineCtx {
             .build();
         let shader_states_infos = [vertex_shader_state_info, fragment_shader_state_info];
         
-        let vertex_input_info = PipelineVertexInputStateCreateInfo::builder().build();
+        // main_vs pulls vertices from the storage buffer at descriptor set 0
+        // binding 0 (indexed by `gl_VertexIndex`) rather than reading fixed
+        // vertex input, so no bindings/attributes are declared here.
+        let vertex_input_info = PipelineVertexInputStateCreateInfo::builder()
+            .build();
         let input_assembly_info = PipelineInputAssemblyStateCreateInfo::builder()
             .topology(PrimitiveTopology::TRIANGLE_LIST)
             .primitive_restart_enable(false)
@@ -95,7 +113,44 @@ impl PipelineCtx {
             .blend_constants([0.0, 0.0, 0.0, 0.0])
             .build();
 
-        let pipeline_layout_info = PipelineLayoutCreateInfo::builder().build();
+        let vertex_descriptor_bindings = [
+            DescriptorSetLayoutBinding::builder()
+                .binding(VERTEX_STORAGE_BUFFER_BINDING)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(ShaderStageFlags::VERTEX)
+                .build(),
+        ];
+        let vertex_descriptor_set_layout_info = DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&vertex_descriptor_bindings)
+            .build();
+        let vertex_descriptor_set_layout = unsafe { render_pass_ctx.swapchain_ctx.device_ctx.logical_info.device.create_descriptor_set_layout(&vertex_descriptor_set_layout_info, None)? };
+
+        let camera_descriptor_bindings = [
+            DescriptorSetLayoutBinding::builder()
+                .binding(CAMERA_VIEW_PROJ_BINDING)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(ShaderStageFlags::VERTEX)
+                .build(),
+            DescriptorSetLayoutBinding::builder()
+                .binding(CAMERA_VIEW_BINDING)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(ShaderStageFlags::VERTEX)
+                .build(),
+        ];
+        let camera_descriptor_set_layout_info = DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&camera_descriptor_bindings)
+            .build();
+        let camera_descriptor_set_layout = unsafe { render_pass_ctx.swapchain_ctx.device_ctx.logical_info.device.create_descriptor_set_layout(&camera_descriptor_set_layout_info, None)? };
+        // Set 0 is the vertex-pulling storage buffer, set 1 is the camera
+        // uniforms, matching the `descriptor_set` indices in shaders/src/lib.rs.
+        let descriptor_set_layouts = [vertex_descriptor_set_layout, camera_descriptor_set_layout];
+
+        let pipeline_layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(&descriptor_set_layouts)
+            .build();
         let pipeline_layout = unsafe { render_pass_ctx.swapchain_ctx.device_ctx.logical_info.device.create_pipeline_layout(&pipeline_layout_info, None)? };
 
         let pipeline_info = GraphicsPipelineCreateInfo::builder()
@@ -122,6 +177,8 @@ impl PipelineCtx {
         Ok(PipelineCtx {
             render_pass_ctx,
             shader_ctx,
+            vertex_descriptor_set_layout,
+            camera_descriptor_set_layout,
             pipeline_layout,
             pipeline
         })
@@ -133,6 +190,8 @@ impl Drop for PipelineCtx {
         unsafe {
             self.render_pass_ctx.swapchain_ctx.device_ctx.logical_info.device.destroy_pipeline(self.pipeline, None);
             self.render_pass_ctx.swapchain_ctx.device_ctx.logical_info.device.destroy_pipeline_layout(self.pipeline_layout, None);
+            self.render_pass_ctx.swapchain_ctx.device_ctx.logical_info.device.destroy_descriptor_set_layout(self.camera_descriptor_set_layout, None);
+            self.render_pass_ctx.swapchain_ctx.device_ctx.logical_info.device.destroy_descriptor_set_layout(self.vertex_descriptor_set_layout, None);
         }
         log::debug!("PipelineCtx dropped");
     }
@@ -1,18 +1,32 @@
 use std::rc::Rc;
 
 use anyhow::Result;
-use ash::vk::{AttachmentDescription, SampleCountFlags, AttachmentLoadOp, ImageLayout, AttachmentReference, SubpassDescription, PipelineBindPoint, AttachmentStoreOp, RenderPassCreateInfo, RenderPass, SubpassDependency, self, AccessFlags, PipelineStageFlags};
+use ash::vk::{AttachmentDescription, SampleCountFlags, AttachmentLoadOp, ImageLayout, AttachmentReference, SubpassDescription, PipelineBindPoint, AttachmentStoreOp, RenderPassCreateInfo, RenderPass, SubpassDependency, self, AccessFlags, PipelineStageFlags, RenderPassMultiviewCreateInfo};
 
 use super::swapchain::SwapchainCtx;
 
 pub struct RenderPassCtx {
     pub swapchain_ctx: Rc<SwapchainCtx>,
-    pub render_pass: RenderPass
+    pub render_pass: RenderPass,
+    /// Number of views rendered per draw via `VK_KHR_multiview`, e.g. 2 for
+    /// stereo VR/side-by-side output. 1 means multiview is disabled.
+    pub view_count: u32
 }
 
 impl RenderPassCtx {
     pub fn new(
         swapchain_ctx: Rc<SwapchainCtx>
+    ) -> Result<Rc<RenderPassCtx>> {
+        Self::new_multiview(swapchain_ctx, 1)
+    }
+
+    /// Like `new`, but renders `view_count` views per draw in a single
+    /// subpass via `VK_KHR_multiview` — each view is selected in the shader
+    /// through `gl_ViewIndex`, driven by the low `view_count` bits of
+    /// `view_mask`. Use `view_count` 2 for stereo VR or side-by-side output.
+    pub fn new_multiview(
+        swapchain_ctx: Rc<SwapchainCtx>,
+        view_count: u32
     ) -> Result<Rc<RenderPassCtx>> {
         let attachment_desc = AttachmentDescription::builder()
             .format(swapchain_ctx.swapchain_image_format)
@@ -42,18 +56,30 @@ impl RenderPassCtx {
             .dst_access_mask(AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE)
             .build();
         let subpass_deps = [subpass_dep];
-        let render_pass_info = RenderPassCreateInfo::builder()
+
+        let view_mask = if view_count > 1 { (1 << view_count) - 1 } else { 0 };
+        let view_masks = [view_mask];
+        let correlation_masks = [view_mask];
+        let mut multiview_info = RenderPassMultiviewCreateInfo::builder()
+            .view_masks(&view_masks)
+            .correlation_masks(&correlation_masks);
+
+        let mut render_pass_info_builder = RenderPassCreateInfo::builder()
             .attachments(&attachment_descs)
             .subpasses(&subpass_descs)
-            .dependencies(&subpass_deps)
-            .build();
+            .dependencies(&subpass_deps);
+        if view_count > 1 {
+            render_pass_info_builder = render_pass_info_builder.push_next(&mut multiview_info);
+        }
+        let render_pass_info = render_pass_info_builder.build();
 
         let render_pass = unsafe { swapchain_ctx.device_ctx.logical_info.device.create_render_pass(&render_pass_info, None)? };
-        
-        log::debug!("RenderPassCtx created");
+
+        log::debug!("RenderPassCtx created (view_count: {})", view_count);
         Ok(Rc::new(RenderPassCtx {
             swapchain_ctx,
-            render_pass
+            render_pass,
+            view_count
         }))
     }
 }